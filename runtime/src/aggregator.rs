@@ -0,0 +1,300 @@
+use rstd::prelude::*;
+
+use codec::{Decode, Encode};
+use rstd::collections::btree_map::BTreeMap;
+use sr_primitives::traits::{One, SimpleArithmetic};
+
+use crate::median::{get_median, get_weighted_median, Median};
+
+/// Reduces the values committed by the sources to one value.
+/// Every value comes with the stake of the source that committed it.
+pub trait Aggregator<Value, Weight>
+{
+    fn aggregate(&self, values: Vec<(Value, Weight)>) -> Option<Value>;
+}
+
+pub struct MedianAggregator;
+pub struct WeightedMedianAggregator;
+pub struct MeanAggregator;
+pub struct TrimmedMeanAggregator
+{
+    /// Percent of values dropped from each side of the sorted values.
+    pub percent: u8,
+}
+pub struct ModeAggregator;
+
+/// Aggregator used by an oracle.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum AggregatorType
+{
+    Median,
+    StakeWeightedMedian,
+    Mean,
+    TrimmedMean(u8),
+    Mode,
+}
+
+impl Default for AggregatorType
+{
+    fn default() -> AggregatorType
+    {
+        AggregatorType::Median
+    }
+}
+
+impl AggregatorType
+{
+    pub fn is_valid(&self) -> bool
+    {
+        match self
+        {
+            AggregatorType::TrimmedMean(percent) => *percent < 50,
+            _ => true,
+        }
+    }
+}
+
+impl<V, W> Aggregator<V, W> for AggregatorType
+where
+    V: SimpleArithmetic + Copy,
+    W: SimpleArithmetic + Copy,
+{
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        match self
+        {
+            AggregatorType::Median => MedianAggregator.aggregate(values),
+            AggregatorType::StakeWeightedMedian => WeightedMedianAggregator.aggregate(values),
+            AggregatorType::Mean => MeanAggregator.aggregate(values),
+            AggregatorType::TrimmedMean(percent) =>
+            {
+                TrimmedMeanAggregator { percent: *percent }.aggregate(values)
+            }
+            AggregatorType::Mode => ModeAggregator.aggregate(values),
+        }
+    }
+}
+
+fn median_value<V: SimpleArithmetic + Copy>(median: Option<Median<V>>) -> Option<V>
+{
+    median.map(Median::value)
+}
+
+/// Mean of `values` rounded down. Never overflows: quotients and remainders
+/// of the division by the count are accumulated separately.
+pub fn get_mean<V: SimpleArithmetic + Copy>(values: &[V]) -> Option<V>
+{
+    if values.is_empty()
+    {
+        return None;
+    }
+
+    let count = V::from(values.len() as u32);
+    let (quotient, _) = values.iter().fold(
+        (V::zero(), V::zero()),
+        |(mut quotient, mut remainder), value| {
+            quotient += *value / count;
+            remainder += *value % count;
+            if remainder >= count
+            {
+                quotient += One::one();
+                remainder -= count;
+            }
+            (quotient, remainder)
+        },
+    );
+
+    Some(quotient)
+}
+
+/// Mean of values weighted by the second element of the pairs, rounded down.
+/// Returns `None` if the sum of weights is zero or an intermediate product overflows.
+pub fn get_weighted_mean<V: SimpleArithmetic + Copy>(values: &[(V, V)]) -> Option<V>
+{
+    let total = values
+        .iter()
+        .try_fold(V::zero(), |total, (_, weight)| total.checked_add(weight))?;
+    if total.is_zero()
+    {
+        return None;
+    }
+
+    let (quotient, _) = values.iter().try_fold(
+        (V::zero(), V::zero()),
+        |(quotient, remainder), (value, weight)| {
+            let quotient = quotient.checked_add(&(*value / total).checked_mul(weight)?)?;
+            let remainder = remainder.checked_add(&(*value % total).checked_mul(weight)?)?;
+            Some((
+                quotient.checked_add(&(remainder / total))?,
+                remainder % total,
+            ))
+        },
+    )?;
+
+    Some(quotient)
+}
+
+impl<V, W> Aggregator<V, W> for MedianAggregator
+where
+    V: SimpleArithmetic + Copy,
+{
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        median_value(get_median(
+            values.into_iter().map(|(value, _)| value).collect(),
+        ))
+    }
+}
+
+impl<V, W> Aggregator<V, W> for WeightedMedianAggregator
+where
+    V: SimpleArithmetic + Copy,
+    W: SimpleArithmetic + Copy,
+{
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        median_value(get_weighted_median(values))
+    }
+}
+
+impl<V, W> Aggregator<V, W> for MeanAggregator
+where
+    V: SimpleArithmetic + Copy,
+{
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        let values: Vec<V> = values.into_iter().map(|(value, _)| value).collect();
+        get_mean(&values)
+    }
+}
+
+impl<V, W> Aggregator<V, W> for TrimmedMeanAggregator
+where
+    V: SimpleArithmetic + Copy,
+{
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        let mut values: Vec<V> = values.into_iter().map(|(value, _)| value).collect();
+        if values.is_empty()
+        {
+            return None;
+        }
+        values.sort();
+
+        let len = values.len();
+        let trim = rstd::cmp::min(len * self.percent as usize / 100, (len - 1) / 2);
+        get_mean(&values[trim..len - trim])
+    }
+}
+
+impl<V, W> Aggregator<V, W> for ModeAggregator
+where
+    V: SimpleArithmetic + Copy,
+{
+    /// The most frequent value. If several values are equally frequent,
+    /// the median of them is returned.
+    fn aggregate(&self, values: Vec<(V, W)>) -> Option<V>
+    {
+        let mut frequencies: BTreeMap<V, u32> = BTreeMap::new();
+        values
+            .into_iter()
+            .for_each(|(value, _)| *frequencies.entry(value).or_insert(0) += 1);
+
+        let max_frequency = frequencies.values().max().cloned().unwrap_or(0);
+        let modes: Vec<V> = frequencies
+            .into_iter()
+            .filter(|(_, frequency)| *frequency == max_frequency)
+            .map(|(value, _)| value)
+            .collect();
+
+        median_value(get_median(modes))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{get_mean, get_weighted_mean, Aggregator, AggregatorType};
+
+    fn aggregate(aggregator: AggregatorType, values: Vec<u128>) -> Option<u128>
+    {
+        aggregator.aggregate(values.into_iter().map(|value| (value, 1u128)).collect())
+    }
+
+    #[test]
+    fn mean()
+    {
+        assert_eq!(get_mean::<u128>(&[]), None);
+        assert_eq!(get_mean(&[1u128, 2, 4]), Some(2));
+        assert_eq!(
+            get_mean(&[u128::max_value(), u128::max_value()]),
+            Some(u128::max_value())
+        );
+        assert_eq!(
+            get_mean(&[u128::max_value(), u128::max_value() - 1]),
+            Some(u128::max_value() - 1)
+        );
+
+        assert_eq!(
+            aggregate(AggregatorType::Mean, vec![1, 2, 3, 100]),
+            Some(26)
+        );
+    }
+
+    #[test]
+    fn weighted_mean()
+    {
+        assert_eq!(get_weighted_mean::<u128>(&[]), None);
+        assert_eq!(get_weighted_mean(&[(10u128, 0)]), None);
+        assert_eq!(get_weighted_mean(&[(10u128, 12), (20, 8)]), Some(14));
+        assert_eq!(get_weighted_mean(&[(10u128, 7), (20, 3)]), Some(13));
+        assert_eq!(get_weighted_mean(&[(1u128, 1), (2, 1)]), Some(1));
+        assert_eq!(
+            get_weighted_mean(&[(u128::max_value(), 1000), (u128::max_value(), 3000)]),
+            Some(u128::max_value())
+        );
+    }
+
+    #[test]
+    fn trimmed_mean()
+    {
+        let values: Vec<u128> = vec![1000, 10, 11, 12, 13, 14, 15, 16, 17, 0];
+        assert_eq!(
+            aggregate(AggregatorType::TrimmedMean(10), values.clone()),
+            Some(13)
+        );
+        assert_eq!(
+            aggregate(AggregatorType::TrimmedMean(0), values.clone()),
+            Some(110)
+        );
+        assert_eq!(aggregate(AggregatorType::TrimmedMean(49), values), Some(13));
+
+        assert!(AggregatorType::TrimmedMean(49).is_valid());
+        assert!(!AggregatorType::TrimmedMean(50).is_valid());
+    }
+
+    #[test]
+    fn mode()
+    {
+        assert_eq!(
+            aggregate(AggregatorType::Mode, vec![5, 1, 5, 2, 5, 2]),
+            Some(5)
+        );
+        assert_eq!(
+            aggregate(AggregatorType::Mode, vec![1, 1, 3, 3, 7, 7, 2]),
+            Some(3)
+        );
+        assert_eq!(aggregate(AggregatorType::Mode, vec![]), None);
+    }
+
+    #[test]
+    fn median()
+    {
+        assert_eq!(aggregate(AggregatorType::Median, vec![3, 1, 2]), Some(2));
+        assert_eq!(
+            AggregatorType::StakeWeightedMedian.aggregate(vec![(1u128, 1u128), (2, 1), (3, 5)]),
+            Some(3)
+        );
+    }
+}
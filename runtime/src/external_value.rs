@@ -1,6 +1,57 @@
 pub use crate::module_trait::*;
 use codec::{Decode, Encode};
 use rstd::cmp::{Ord, Ordering};
+use rstd::prelude::*;
+use sr_primitives::traits::{Bounded, CheckedMul, IntegerSquareRoot};
+
+use crate::aggregator::get_mean;
+use crate::outlier::get_mad;
+
+/// Statistics of the source values that a calculated value is aggregated from.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ValueMetadata<T: Trait>
+{
+    pub sources_count: u32,
+    pub min: T::ValueType,
+    pub max: T::ValueType,
+    /// Median absolute deviation of the source values.
+    pub mad: T::ValueType,
+    /// Population standard deviation of the source values, saturated at the maximum value.
+    pub std_dev: T::ValueType,
+}
+
+impl<T: Trait> ValueMetadata<T>
+{
+    pub fn from_values(values: &[T::ValueType]) -> Option<ValueMetadata<T>>
+    {
+        let mean = get_mean(values)?;
+        let squared_deviations: Vec<T::ValueType> = values
+            .iter()
+            .map(|value| {
+                let deviation = if *value > mean
+                {
+                    *value - mean
+                }
+                else
+                {
+                    mean - *value
+                };
+                deviation
+                    .checked_mul(&deviation)
+                    .unwrap_or_else(Bounded::max_value)
+            })
+            .collect();
+
+        Some(ValueMetadata {
+            sources_count: values.len() as u32,
+            min: *values.iter().min()?,
+            max: *values.iter().max()?,
+            mad: get_mad(values).map(|(_, mad)| mad)?,
+            std_dev: get_mean(&squared_deviations)?.integer_sqrt(),
+        })
+    }
+}
 
 #[derive(Encode, Decode, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -8,6 +59,8 @@ pub struct ExternalValue<T: Trait>
 {
     pub value: Option<T::ValueType>,
     pub last_changed: Option<Moment<T>>,
+    /// Set only for values calculated by an oracle.
+    pub metadata: Option<ValueMetadata<T>>,
 }
 
 impl<T: Trait> PartialOrd for ExternalValue<T>
@@ -25,6 +78,7 @@ impl<T: Trait> ExternalValue<T>
         ExternalValue {
             value: None,
             last_changed: None,
+            metadata: None,
         }
     }
 
@@ -33,6 +87,7 @@ impl<T: Trait> ExternalValue<T>
         ExternalValue {
             value: Some(value),
             last_changed: Some(timestamp::Module::<T>::get()),
+            metadata: None,
         }
     }
 
@@ -40,6 +95,7 @@ impl<T: Trait> ExternalValue<T>
     {
         self.value = None;
         self.last_changed = None;
+        self.metadata = None;
     }
 
     pub fn update_time(&mut self, now: Moment<T>)
@@ -56,6 +112,17 @@ impl<T: Trait> ExternalValue<T>
         self.value = Some(value);
         self.update_time(now);
     }
+
+    pub fn update_with_metadata(
+        &mut self,
+        value: T::ValueType,
+        metadata: ValueMetadata<T>,
+        now: Moment<T>,
+    )
+    {
+        self.update(value, now);
+        self.metadata = Some(metadata);
+    }
 }
 
 impl<T: Trait> Default for ExternalValue<T>
@@ -65,6 +132,7 @@ impl<T: Trait> Default for ExternalValue<T>
         ExternalValue {
             value: None,
             last_changed: None,
+            metadata: None,
         }
     }
 }
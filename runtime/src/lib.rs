@@ -15,17 +15,22 @@ use primitives::OpaqueMetadata;
 use rstd::prelude::*;
 use sr_api::impl_runtime_apis;
 use sr_primitives::traits::{
-    BlakeTwo256, Block as BlockT, ConvertInto, IdentifyAccount, NumberFor, StaticLookup, Verify,
+    BlakeTwo256, Block as BlockT, ConvertInto, Extrinsic, IdentifyAccount, NumberFor,
+    SaturatedConversion, StaticLookup, Verify,
 };
 use sr_primitives::weights::Weight;
 use sr_primitives::{
     create_runtime_str, generic, impl_opaque_keys, transaction_validity::TransactionValidity,
     ApplyResult, MultiSignature,
 };
+use support::traits::Get;
+use system::offchain::TransactionSubmitter;
 #[cfg(feature = "std")]
 use version::NativeVersion;
 use version::RuntimeVersion;
 
+use module::ExternalValue;
+
 // A few exports that help ease life for downstream crates.
 pub use assets::Call as AssetsCall;
 pub use balances::Call as BalancesCall;
@@ -58,15 +63,30 @@ pub type Index = u32;
 /// A hash of some data used by the chain.
 pub type Hash = primitives::H256;
 
+/// Timestamp in milliseconds, also the type of oracle period numbers.
+pub type Moment = u64;
+
+/// Id of an oracle.
+pub type OracleId = u64;
+
+/// Id of a tablescore table.
+pub type TableId = u64;
+
 /// Digest item type.
 pub type DigestItem = generic::DigestItem<Hash>;
 
+pub mod aggregator;
 pub mod external_value;
 pub mod median;
 pub mod module;
 pub mod module_trait;
+pub mod offchain;
 pub mod oracle;
+pub mod outlier;
 pub mod period_handler;
+pub mod reward;
+pub mod runtime_api;
+pub mod slashing;
 pub mod tablescore;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
@@ -99,7 +119,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("node"), // ToDo Rename
     impl_name: create_runtime_str!("test-node"),
     authoring_version: 1,
-    spec_version: 1,
+    spec_version: 2,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
 };
@@ -168,7 +188,7 @@ impl system::Trait for Runtime
 impl timestamp::Trait for Runtime
 {
     /// A timestamp: milliseconds since the unix epoch.
-    type Moment = u64;
+    type Moment = Moment;
     type OnTimestampSet = Aura;
     type MinimumPeriod = MinimumPeriod;
 }
@@ -250,15 +270,61 @@ impl assets::Trait for Runtime
 impl tablescore::Trait for Runtime
 {
     type Event = Event;
-    type TableId = u64;
+    type TableId = TableId;
     type TargetType = AccountId;
 }
 
+parameter_types! {
+    pub const HistoryDepth: u32 = 1024;
+    pub const MaxOraclesPerBlock: u32 = 16;
+    pub const ClosingGracePeriod: u64 = 7 * 24 * 60 * 60 * 1000;
+    pub const SlashDestination: Option<AccountId> = None;
+}
+
 impl module::Trait for Runtime
 {
     type Event = Event;
-    type OracleId = u64;
+    type OracleId = OracleId;
     type ValueType = u128;
+    type HistoryDepth = HistoryDepth;
+    type MaxOraclesPerBlock = MaxOraclesPerBlock;
+    type ClosingGracePeriod = ClosingGracePeriod;
+    type SlashDestination = SlashDestination;
+    type Call = Call;
+    type SubmitTransaction =
+        TransactionSubmitter<module::crypto::Public, Runtime, UncheckedExtrinsic>;
+    type AuthorityId = module::crypto::Public;
+}
+
+impl system::offchain::CreateTransaction<Runtime, UncheckedExtrinsic> for Runtime
+{
+    type Public = <Signature as Verify>::Signer;
+    type Signature = Signature;
+
+    fn create_transaction<F: system::offchain::Signer<Self::Public, Self::Signature>>(
+        call: Call,
+        public: Self::Public,
+        account: AccountId,
+        index: Index,
+    ) -> Option<(Call, <UncheckedExtrinsic as Extrinsic>::SignaturePayload)>
+    {
+        let period = BlockHashCount::get() as u64;
+        let current_block = System::block_number().saturated_into::<u64>();
+        let tip = 0;
+        let extra: SignedExtra = (
+            system::CheckVersion::<Runtime>::new(),
+            system::CheckGenesis::<Runtime>::new(),
+            system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
+            system::CheckNonce::<Runtime>::from(index),
+            system::CheckWeight::<Runtime>::new(),
+            transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+        );
+        let raw_payload = generic::SignedPayload::new(call, extra).ok()?;
+        let signature = F::sign(public, &raw_payload)?;
+        let address = Indices::unlookup(account);
+        let (call, extra, _) = raw_payload.deconstruct();
+        Some((call, (address, signature, extra)))
+    }
 }
 
 construct_runtime!(
@@ -276,8 +342,8 @@ construct_runtime!(
             TransactionPayment: transaction_payment::{Module, Storage},
             Sudo: sudo,
             Assets: assets::{Module, Call, Storage, Event<T>},
-            Tablescore: tablescore::{Module, Call, Storage, Event<T>},
-            Oracle: module::{Module, Call, Storage, Event<T>},
+            Tablescore: tablescore::{Module, Call, Storage, Event<T>, Error},
+            Oracle: module::{Module, Call, Storage, Event<T>, Error},
             RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
     }
 );
@@ -388,6 +454,54 @@ impl_runtime_apis! {
             Grandpa::grandpa_authorities()
         }
     }
+
+    impl runtime_api::OracleApi<Block, AccountId, OracleId, ExternalValue<Runtime>, Moment> for Runtime {
+        fn get_oracles() -> Vec<(OracleId, Vec<u8>)> {
+            Oracle::get_oracles()
+        }
+
+        fn get_values(oracle_id: OracleId) -> Vec<(u8, ExternalValue<Runtime>)> {
+            Oracle::get_values(oracle_id)
+        }
+
+        fn get_assets(oracle_id: OracleId) -> Vec<(u8, Vec<u8>, bool)> {
+            Oracle::get_assets(oracle_id)
+        }
+
+        fn get_sources(oracle_id: OracleId) -> Vec<AccountId> {
+            Oracle::get_current_sources(oracle_id)
+        }
+
+        fn get_current_period(oracle_id: OracleId) -> Option<Moment> {
+            Oracle::get_current_period(oracle_id)
+        }
+
+        fn is_aggregate_time(oracle_id: OracleId) -> bool {
+            Oracle::is_aggregate_time(oracle_id)
+        }
+
+        fn get_time_to_aggregate(oracle_id: OracleId) -> Option<Moment> {
+            Oracle::get_time_to_aggregate(oracle_id)
+        }
+
+        fn can_commit(oracle_id: OracleId, account: AccountId) -> bool {
+            Oracle::can_commit(oracle_id, &account)
+        }
+    }
+
+    impl runtime_api::TablescoreApi<Block, AccountId, TableId, AccountId, Balance> for Runtime {
+        fn get_head(table_id: TableId) -> Vec<(AccountId, Balance)> {
+            Tablescore::get_head_with_stake(&table_id)
+        }
+
+        fn get_leaderboard(table_id: TableId) -> Vec<(AccountId, Balance)> {
+            Tablescore::get_leaderboard(&table_id)
+        }
+
+        fn get_vote(table_id: TableId, voter: AccountId) -> Option<(AccountId, Balance)> {
+            Tablescore::get_vote(&table_id, &voter)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -2,7 +2,10 @@ use rstd::prelude::*;
 
 extern crate alloc;
 
-use rstd::cmp::Ord;
+use rstd::cmp::{Ord, Ordering};
+use sr_primitives::traits::SimpleArithmetic;
+
+const GROUP_SIZE: usize = 5;
 
 #[derive(PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -12,23 +15,190 @@ pub enum Median<T>
     Pair(T, T),
 }
 
-pub fn get_median<T: Ord + Copy>(mut values: Vec<T>) -> Option<Median<T>>
+impl<T: SimpleArithmetic + Copy> Median<T>
 {
-    values.sort();
+    /// The median as one value: the average of the pair for an even count of values.
+    pub fn value(self) -> T
+    {
+        match self
+        {
+            Median::Value(value) => value,
+            Median::Pair(left, right) => average(left, right),
+        }
+    }
+}
 
+/// Average of two values rounded down, without overflow of `left + right`.
+pub fn average<T: SimpleArithmetic + Copy>(left: T, right: T) -> T
+{
+    let two = T::from(2u8);
+    left / two + right / two + (left % two + right % two) / two
+}
+
+pub fn get_median<T: Ord + Copy>(mut values: Vec<T>) -> Option<Median<T>>
+{
     let middle = values.len() / 2;
     match values.len()
     {
-        0 | 1 => None,
-        len if len % 2 == 0 => Some(Median::Pair(values[middle - 1], values[middle + 1])),
-        _len => Some(Median::Value(values[middle])),
+        0 => None,
+        len if len % 2 == 0 =>
+        {
+            let right = select(&mut values, middle);
+            // After selection every value before `middle` is not greater than `right`
+            values[..middle]
+                .iter()
+                .max()
+                .map(|left| Median::Pair(*left, right))
+        }
+        _len => Some(Median::Value(select(&mut values, middle))),
+    }
+}
+
+/// Returns the value that would be at position `index` in sorted `values`.
+/// Values are reordered so that values before `index` are not greater and values
+/// after `index` are not less than it. Runs in linear time (median of medians).
+///
+/// Panics if `index` is out of bounds.
+pub fn select<T: Ord + Copy>(values: &mut [T], index: usize) -> T
+{
+    if values.len() <= GROUP_SIZE
+    {
+        values.sort();
+        return values[index];
+    }
+
+    let pivot = get_pivot(values);
+    let (less_end, greater_begin) = partition(values, pivot);
+
+    if index < less_end
+    {
+        select(&mut values[..less_end], index)
+    }
+    else if index < greater_begin
+    {
+        pivot
+    }
+    else
+    {
+        select(&mut values[greater_begin..], index - greater_begin)
+    }
+}
+
+fn get_pivot<T: Ord + Copy>(values: &[T]) -> T
+{
+    let mut medians: Vec<T> = values
+        .chunks(GROUP_SIZE)
+        .map(|chunk| {
+            let mut group = chunk.to_vec();
+            group.sort();
+            group[group.len() / 2]
+        })
+        .collect();
+
+    let middle = medians.len() / 2;
+    select(&mut medians, middle)
+}
+
+/// Three-way partition around `pivot`. Returns the end of the values less than
+/// `pivot` and the beginning of the values greater than `pivot`.
+fn partition<T: Ord + Copy>(values: &mut [T], pivot: T) -> (usize, usize)
+{
+    let (mut less_end, mut current, mut greater_begin) = (0, 0, values.len());
+    while current < greater_begin
+    {
+        match values[current].cmp(&pivot)
+        {
+            Ordering::Less =>
+            {
+                values.swap(less_end, current);
+                less_end += 1;
+                current += 1;
+            }
+            Ordering::Greater =>
+            {
+                greater_begin -= 1;
+                values.swap(current, greater_begin);
+            }
+            Ordering::Equal => current += 1,
+        }
+    }
+    (less_end, greater_begin)
+}
+
+/// Median of `values`, where each value counts as many times as its weight.
+/// Returns a `Median::Pair` when the weights split exactly in half between two values.
+pub fn get_weighted_median<T, W>(mut values: Vec<(T, W)>) -> Option<Median<T>>
+where
+    T: Ord + Copy,
+    W: SimpleArithmetic + Copy,
+{
+    values.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let total = values
+        .iter()
+        .fold(W::zero(), |sum, (_, weight)| sum.saturating_add(*weight));
+
+    let mut accumulated = W::zero();
+    for (index, (value, weight)) in values.iter().enumerate()
+    {
+        if weight.is_zero()
+        {
+            continue;
+        }
+
+        accumulated = accumulated.saturating_add(*weight);
+        match accumulated.cmp(&(total - accumulated))
+        {
+            Ordering::Less => continue,
+            Ordering::Equal =>
+            {
+                return values[index + 1..]
+                    .iter()
+                    .find(|(_, weight)| !weight.is_zero())
+                    .map(|(next, _)| Median::Pair(*value, *next));
+            }
+            Ordering::Greater => return Some(Median::Value(*value)),
+        }
     }
+
+    None
 }
 
 #[cfg(test)]
 mod tests
 {
-    use super::{get_median, Median};
+    use super::{average, get_median, get_weighted_median, select, Median};
+
+    /// Deterministic xorshift generator, so failures are reproducible.
+    struct Generator(u64);
+
+    impl Generator
+    {
+        fn next(&mut self) -> u64
+        {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn values(&mut self, len: usize, range: u64) -> Vec<u64>
+        {
+            (0..len).map(|_| self.next() % range).collect()
+        }
+    }
+
+    fn reference_median(mut values: Vec<u64>) -> Option<Median<u64>>
+    {
+        values.sort();
+        let middle = values.len() / 2;
+        match values.len()
+        {
+            0 => None,
+            len if len % 2 == 0 => Some(Median::Pair(values[middle - 1], values[middle])),
+            _len => Some(Median::Value(values[middle])),
+        }
+    }
 
     #[test]
     fn simple()
@@ -37,4 +207,83 @@ mod tests
         let median = array[5];
         assert_eq!(get_median(array), Some(Median::Value(median)));
     }
+
+    #[test]
+    fn edge_cases()
+    {
+        assert_eq!(get_median(Vec::<u8>::new()), None);
+        assert_eq!(get_median(vec![7u8]), Some(Median::Value(7)));
+        assert_eq!(get_median(vec![9u8, 3]), Some(Median::Pair(3, 9)));
+        assert_eq!(get_median(vec![4u8, 1, 3, 2]), Some(Median::Pair(2, 3)));
+    }
+
+    #[test]
+    fn average_without_overflow()
+    {
+        assert_eq!(
+            average(u128::max_value(), u128::max_value()),
+            u128::max_value()
+        );
+        assert_eq!(
+            average(u128::max_value(), u128::max_value() - 1),
+            u128::max_value() - 1
+        );
+        assert_eq!(average(3u128, 4), 3);
+        assert_eq!(average(3u128, 5), 4);
+        assert_eq!(Median::Pair(1u128, 2).value(), 1);
+        assert_eq!(Median::Value(5u128).value(), 5);
+    }
+
+    #[test]
+    fn median_matches_reference()
+    {
+        let mut generator = Generator(0x2545_f491_4f6c_dd1d);
+        for len in 0..200
+        {
+            for &range in [2, 10, 1000, u64::max_value()].iter()
+            {
+                let values = generator.values(len, range);
+                assert_eq!(
+                    get_median(values.clone()),
+                    reference_median(values.clone()),
+                    "values: {:?}",
+                    values
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn select_matches_reference()
+    {
+        let mut generator = Generator(0x9e37_79b9_7f4a_7c15);
+        for len in 1..100
+        {
+            let values = generator.values(len, 50);
+            let mut sorted = values.clone();
+            sorted.sort();
+
+            for index in 0..len
+            {
+                let mut selected = values.clone();
+                let value = select(&mut selected, index);
+                assert_eq!(value, sorted[index]);
+                assert!(selected[..index].iter().all(|other| *other <= value));
+                assert!(selected[index + 1..].iter().all(|other| *other >= value));
+            }
+        }
+    }
+
+    #[test]
+    fn weighted()
+    {
+        let values: Vec<(u8, u128)> = vec![(1, 1), (2, 1), (3, 10), (4, 1)];
+        assert_eq!(get_weighted_median(values), Some(Median::Value(3)));
+
+        let values: Vec<(u8, u128)> = vec![(10, 5), (1, 2), (2, 3)];
+        assert_eq!(get_weighted_median(values), Some(Median::Pair(2, 10)));
+
+        let values: Vec<(u8, u128)> = vec![(1, 0), (2, 0)];
+        assert_eq!(get_weighted_median(values), None);
+    }
 }
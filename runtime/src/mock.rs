@@ -5,15 +5,19 @@ pub use crate::tablescore::*;
 pub use assets::Call as AssetsCall;
 use aura_primitives::sr25519::AuthorityId as AuraId;
 pub use balances::Call as BalancesCall;
-use sr_primitives::traits::{BlakeTwo256, ConvertInto};
+use codec::Encode;
+use sr_primitives::testing::{TestXt, UintAuthorityId};
+use sr_primitives::traits::{BlakeTwo256, ConvertInto, IdentifyAccount};
 use sr_primitives::weights::Weight;
 #[cfg(any(feature = "std", test))]
 pub use sr_primitives::BuildStorage;
 use sr_primitives::{generic, traits::IdentityLookup};
 pub use sr_primitives::{Perbill, Permill};
 pub use support::{
-    construct_runtime, impl_outer_origin, parameter_types, traits::Randomness, StorageValue,
+    construct_runtime, impl_outer_event, impl_outer_origin, parameter_types, traits::Randomness,
+    StorageValue,
 };
+use system::offchain::{CreateTransaction, Signer, TransactionSubmitter};
 use system::IsDeadAccount;
 pub use timestamp::Call as TimestampCall;
 
@@ -32,6 +36,12 @@ impl_outer_origin! {
     pub enum Origin for Test  where system = system {}
 }
 
+impl_outer_event! {
+    pub enum TestEvent for Test {
+        grandpa, indices<T>, balances<T>, sudo<T>, assets<T>, tablescore<T>, module<T>,
+    }
+}
+
 parameter_types! {
     pub const BlockHashCount: BlockNumber = 250;
     pub const MaximumBlockWeight: Weight = 1_000_000;
@@ -57,7 +67,7 @@ impl system::Trait for Test
     type MaximumBlockLength = MaximumBlockLength;
     type AvailableBlockRatio = AvailableBlockRatio;
     type Call = tablescore::Call<Test>;
-    type Event = ();
+    type Event = TestEvent;
     type Version = ();
 }
 
@@ -75,7 +85,7 @@ impl aura::Trait for Test
 
 impl grandpa::Trait for Test
 {
-    type Event = ();
+    type Event = TestEvent;
 }
 
 pub struct TestIsDeadAccount {}
@@ -92,7 +102,7 @@ impl indices::Trait for Test
     type AccountIndex = AccountIndex;
     type ResolveHint = indices::SimpleResolveHint<AccountId, AccountIndex>;
     type IsDeadAccount = TestIsDeadAccount;
-    type Event = ();
+    type Event = TestEvent;
 }
 
 parameter_types! {
@@ -110,7 +120,7 @@ impl balances::Trait for Test
     type Balance = Balance;
     type OnFreeBalanceZero = ();
     type OnNewAccount = ();
-    type Event = ();
+    type Event = TestEvent;
     type DustRemoval = ();
     type TransferPayment = ();
     type ExistentialDeposit = ExistentialDeposit;
@@ -135,33 +145,102 @@ impl transaction_payment::Trait for Test
 
 impl sudo::Trait for Test
 {
-    type Event = ();
+    type Event = TestEvent;
     type Proposal = tablescore::Call<Test>;
 }
 
 impl assets::Trait for Test
 {
-    type Event = ();
+    type Event = TestEvent;
     type Balance = u128;
     type AssetId = u64;
 }
 
 impl tablescore::Trait for Test
 {
-    type Event = ();
+    type Event = TestEvent;
     type TargetType = u64;
     type TableId = u64;
 }
 
+parameter_types! {
+    pub const HistoryDepth: u32 = 2;
+    pub const MaxOraclesPerBlock: u32 = 1;
+    pub const ClosingGracePeriod: u64 = 100;
+    pub const SlashDestination: Option<u64> = Some(TREASURY);
+}
+
 impl oracle::Trait for Test
 {
-    type Event = ();
+    type Event = TestEvent;
     type OracleId = u64;
     type ValueType = u128;
+    type HistoryDepth = HistoryDepth;
+    type MaxOraclesPerBlock = MaxOraclesPerBlock;
+    type ClosingGracePeriod = ClosingGracePeriod;
+    type SlashDestination = SlashDestination;
+    type Call = crate::module::Call<Test>;
+    type SubmitTransaction = TransactionSubmitter<TestSigner, Test, Extrinsic>;
+    type AuthorityId = UintAuthorityId;
+}
+
+/// Extrinsic of the offchain worker, signed by the account id.
+pub type Extrinsic = TestXt<crate::module::Call<Test>, ()>;
+
+/// Offchain worker key of the account with the same id.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TestPublic(pub AccountId);
+
+impl From<UintAuthorityId> for TestPublic
+{
+    fn from(key: UintAuthorityId) -> Self
+    {
+        TestPublic(key.0)
+    }
+}
+
+impl IdentifyAccount for TestPublic
+{
+    type AccountId = AccountId;
+
+    fn into_account(self) -> AccountId
+    {
+        self.0
+    }
+}
+
+pub struct TestSigner;
+
+impl Signer<TestPublic, ()> for TestSigner
+{
+    fn sign<Payload: Encode>(_public: TestPublic, _payload: &Payload) -> Option<()>
+    {
+        Some(())
+    }
+}
+
+impl CreateTransaction<Test, Extrinsic> for Test
+{
+    type Public = TestPublic;
+    type Signature = ();
+
+    fn create_transaction<F: Signer<Self::Public, Self::Signature>>(
+        call: crate::module::Call<Test>,
+        public: Self::Public,
+        account: AccountId,
+        _index: Index,
+    ) -> Option<(crate::module::Call<Test>, (AccountId, ()))>
+    {
+        F::sign(public, &call)?;
+        Some((call, (account, ())))
+    }
 }
 
+pub type AssetsModule = assets::Module<Test>;
 pub type OracleModule = crate::module::Module<Test>;
 pub type TablescoreModule = crate::tablescore::Module<Test>;
+pub type TimestampModule = timestamp::Module<Test>;
+pub type System = system::Module<Test>;
 
 pub const ASSET_ID: u64 = 123;
 pub const BALANCE: Balance = 1000;
@@ -169,6 +248,8 @@ pub const BALANCE: Balance = 1000;
 pub const ALICE: AccountId = 1;
 pub const BOB: AccountId = 2;
 pub const CAROL: AccountId = 3;
+/// Receives slashed stakes, has no assets initially.
+pub const TREASURY: AccountId = 4;
 
 pub fn new_test_ext() -> runtime_io::TestExternalities
 {
@@ -187,5 +268,8 @@ pub fn new_test_ext() -> runtime_io::TestExternalities
     .assimilate_storage(&mut t)
     .unwrap();
 
-    t.into()
+    let mut ext: runtime_io::TestExternalities = t.into();
+    // Events are not deposited in the genesis block
+    ext.execute_with(|| System::set_block_number(1));
+    ext
 }
@@ -1,27 +1,211 @@
-use support::{decl_event, decl_module, decl_storage, dispatch::Result as SimpleResult};
+use support::{decl_error, decl_event, decl_module, decl_storage};
 
+use codec::Encode;
+use primitives::crypto::KeyTypeId;
+use rstd::cmp::{max, min};
+use rstd::collections::btree_map::BTreeMap;
+use rstd::convert::{TryFrom, TryInto};
 use rstd::prelude::*;
 use rstd::result::Result;
-use sr_primitives::traits::{CheckedAdd, One};
-use system::ensure_signed;
+use sr_primitives::traits::{
+    AccountIdConversion, Bounded, CheckedAdd, IdentifyAccount, One, Saturating, Zero,
+};
+use sr_primitives::{ModuleId, Permill, RuntimeAppPublic};
+use support::traits::Get;
+use system::offchain::{PublicOf, SubmitSignedTransaction};
+use system::{ensure_root, ensure_signed};
 
+use crate::aggregator::get_weighted_mean;
+use crate::offchain;
+use crate::reward::split_reward;
 pub use crate::oracle::*;
 use crate::tablescore;
 
+/// Holds the reward pools of all oracles.
+const MODULE_ID: ModuleId = ModuleId(*b"dpos/orc");
+
+/// Key type of the keys that sign the commits of the offchain worker.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"orcl");
+
+/// Keys of the offchain worker.
+pub mod crypto
+{
+    use super::KEY_TYPE;
+    use app_crypto::{app_crypto, sr25519};
+    use sr_primitives::MultiSigner;
+
+    app_crypto!(sr25519, KEY_TYPE);
+
+    impl From<Public> for MultiSigner
+    {
+        fn from(key: Public) -> Self
+        {
+            sr25519::Public::from(key).into()
+        }
+    }
+}
+
 decl_storage! {
     trait Store for Module<T: Trait> as Tablescore
     {
         pub Oracles get(oracles): map T::OracleId => Oracle<T>;
 
         OracleIdSequnce get(next_oracle_id): T::OracleId;
+        /// Oracle from which the automatic calculation continues in the next block.
+        NextCalculatedOracle get(next_calculated_oracle): T::OracleId;
+        /// Period of the last automatic calculation of the oracle. A failed calculation
+        /// is not retried until the next period.
+        pub LastCalculatePeriods get(last_calculate_period): map T::OracleId => Option<Moment<T>>;
+
+        /// Calculated values by `(oracle_id, external_asset_id, period)`.
+        pub ValuesHistory get(values_history): map (T::OracleId, u8, Moment<T>) => Option<ExternalValue<T>>;
+        /// Periods stored in `ValuesHistory` by `(oracle_id, external_asset_id)`, oldest first.
+        pub HistoryPeriods get(history_periods): map (T::OracleId, u8) => Vec<Moment<T>>;
+
+        /// Balance of the reward pool of the oracle, in the asset of its reward rule.
+        pub RewardPools get(reward_pool): map T::OracleId => Balance<T>;
+        /// Period and the sources slashed in it. A source is slashed at most once per period,
+        /// even if its values of several assets deviate.
+        pub SlashedSources get(slashed_sources): map T::OracleId => (Moment<T>, Vec<AccountId<T>>);
+        /// Part of the rewards that the source keeps before sharing them with its voters.
+        pub SourceCommissions get(source_commission): map AccountId<T> => Permill;
+    }
+}
+
+decl_error! {
+    /// Errors of the oracle module.
+    pub enum Error
+    {
+        /// There is no oracle with this id.
+        OracleNotFound,
+        /// No more ids for oracles.
+        OracleIdOverflow,
+        /// No more ids for tables of oracles.
+        TableIdOverflow,
+        /// Aggregator params are out of range.
+        WrongAggregatorParams,
+        /// Calculate period must be longer than aggregate period, and aggregate period
+        /// longer than reveal period.
+        WrongPeriods,
+        /// Reveal period must be shorter than aggregate period.
+        WrongRevealPeriod,
+        /// An oracle can have at most 255 assets.
+        TooManyAssets,
+        /// Asset ids of the oracle are exhausted.
+        AssetIdsExhausted,
+        /// There is no active asset with this id.
+        WrongAssetId,
+        /// The asset is deprecated.
+        DeprecatedAsset,
+        /// Only a deprecated asset can be removed.
+        NotDeprecatedAsset,
+        /// Number of values doesn't match the number of assets.
+        AssetsCountMismatch,
+        /// Threshold must be positive and not greater than the sources threshold.
+        WrongThreshold,
+        /// There are fewer sources in the table than the sources threshold.
+        TooFewSources,
+        /// There are fewer actual values of the asset than its threshold.
+        TooFewCommittedValues,
+        /// The value can't be calculated.
+        CalculationError,
+        /// The caller is not a source of the oracle.
+        NotSource,
+        /// The source has already committed values in this block.
+        AlreadyCommitted,
+        /// The caller is neither the owner of the oracle nor root.
+        NotOwner,
+        /// The oracle is closed.
+        OracleClosed,
+        /// The oracle is not closed.
+        OracleNotClosed,
+        /// The grace period of the closed oracle has not passed.
+        GracePeriodNotPassed,
+        /// Commits are accepted only in the aggregation window.
+        NotAggregateTime,
+        /// The calculation time has not come, the old value is actual.
+        NotCalculateTime,
+        /// The oracle accepts only hashed commits.
+        OnlyHashedCommits,
+        /// The oracle doesn't use the commit-reveal mode.
+        NotCommitReveal,
+        /// Hashes are committed only before the reveal sub-window.
+        NotCommitTime,
+        /// Values are revealed only in the reveal sub-window.
+        NotRevealTime,
+        /// Revealed values don't match the committed hash.
+        WrongReveal,
+        /// The oracle has no reward rule.
+        NoRewardRule,
+        /// The reward pool can't be topped up with zero amount.
+        ZeroTopUp,
+        /// The reward asset can't be changed while the reward pool is not empty.
+        RewardPoolNotEmpty,
     }
 }
 
+/// Module errors can't carry data, so the required and the actual counts
+/// of `TooFewCommitedValue` and `TooFewAccounts` are printed to the node log.
+impl From<OracleError> for Error
+{
+    fn from(error: OracleError) -> Error
+    {
+        match error
+        {
+            OracleError::CalculationError => Error::CalculationError,
+            OracleError::TooFewCommitedValue(threshold, count) =>
+            {
+                print_counts(
+                    "Too few committed values, required and committed:",
+                    threshold,
+                    count,
+                );
+                Error::TooFewCommittedValues
+            }
+            OracleError::WrongAssetId(_) => Error::WrongAssetId,
+            OracleError::TooFewAccounts(threshold, count) =>
+            {
+                print_counts("Too few sources, required and available:", threshold, count);
+                Error::TooFewSources
+            }
+            OracleError::AccountAccess => Error::NotSource,
+            OracleError::WrongThreshold(_) => Error::WrongThreshold,
+            OracleError::WrongReveal => Error::WrongReveal,
+            OracleError::DeprecatedAsset(_) => Error::DeprecatedAsset,
+            OracleError::NotDeprecatedAsset(_) => Error::NotDeprecatedAsset,
+            OracleError::TooManyAssets => Error::AssetIdsExhausted,
+        }
+    }
+}
+
+fn print_counts(message: &str, required: usize, actual: usize)
+{
+    sr_primitives::print(message);
+    sr_primitives::print(required);
+    sr_primitives::print(actual);
+}
+
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin
     {
+        type Error = Error;
+
         fn deposit_event() = default;
 
+        const HistoryDepth: u32 = T::HistoryDepth::get();
+        const MaxOraclesPerBlock: u32 = T::MaxOraclesPerBlock::get();
+        const ClosingGracePeriod: Moment<T> = T::ClosingGracePeriod::get();
+
+        fn on_finalize(_block: T::BlockNumber)
+        {
+            Self::calculate_oracles(timestamp::Module::<T>::get());
+        }
+
+        fn offchain_worker(_block: T::BlockNumber)
+        {
+            Self::commit_offchain_values(timestamp::Module::<T>::get());
+        }
+
         pub fn create(
             origin,
             name: RawString,
@@ -29,85 +213,463 @@ decl_module! {
             source_calculate_count: u8,
             aggregate_period: TimeInterval<T>,
             calculate_period: TimeInterval<T>,
-            assets: AssetsVec<RawString>) -> SimpleResult
+            aggregator: AggregatorType,
+            outlier_filter: OutlierFilter,
+            staleness: StalenessPolicy,
+            assets: AssetsVec<RawString>,
+            assets_threshold: Option<AssetsVec<u8>>) -> Result<(), Error>
         {
-            let _ = ensure_signed(origin)?;
-            let table = tablescore::Module::<T>::create(asset_id, source_calculate_count, Some(name.clone()))?;
+            let who = ensure_signed(origin)?;
+            if !aggregator.is_valid()
+            {
+                return Err(Error::WrongAggregatorParams);
+            }
+            if assets.0.len() > u8::max_value() as usize
+            {
+                return Err(Error::TooManyAssets);
+            }
+            let period_handler = PeriodHandler::new(timestamp::Module::<T>::get(), calculate_period, aggregate_period)
+                .map_err(|_| Error::WrongPeriods)?;
+            let mut oracle = Oracle::new(name.clone(), who.clone(), TableId::<T>::default(), period_handler, source_calculate_count, aggregator, outlier_filter, staleness, assets);
+            if let Some(thresholds) = assets_threshold
+            {
+                oracle.set_assets_threshold(thresholds)?;
+            }
 
-            let period_handler = PeriodHandler::new(timestamp::Module::<T>::get(), calculate_period, aggregate_period)?;
-            Oracles::<T>::insert(Self::pop_new_oracle_id()?,
-                Oracle::new(name, table, period_handler, source_calculate_count, assets),
-            );
+            oracle.table = tablescore::Module::<T>::create(asset_id, source_calculate_count, Some(name))
+                .map_err(|_| Error::TableIdOverflow)?;
+            let oracle_id = Self::pop_new_oracle_id()?;
+            Oracles::<T>::insert(oracle_id, oracle);
 
+            Self::deposit_event(RawEvent::OracleCreated(oracle_id, who));
             Ok(())
         }
 
+        /// Commit values of all active assets, in the order of `assets_id` of the oracle
+        /// without deprecated assets.
         pub fn commit(
             origin,
             oracle_id: T::OracleId,
             values: AssetsVec<T::ValueType>,
-        ) -> SimpleResult
+        ) -> Result<(), Error>
         {
             let who = ensure_signed(origin)?;
-
-            let oracle = Oracles::<T>::get(oracle_id);
-
             let now = timestamp::Module::<T>::get();
 
-            if oracle.period_handler.is_sources_update_needed(now)
+            let oracle = Self::prepare_commit(&who, oracle_id, now)?;
+            if oracle.is_commit_reveal()
             {
-                let mut result = Err("Can't find oracle");
+                Err(Error::OnlyHashedCommits)
+            }
+            else if values.0.len() != oracle.get_active_assets_count()
+            {
+                Err(Error::AssetsCountMismatch)
+            }
+            else
+            {
+                let committed = Self::get_committed_values(&oracle, &values);
+                let mut result = Err(Error::OracleNotFound);
                 Oracles::<T>::mutate(oracle_id, |oracle| {
-                    result = oracle.update_accounts(tablescore::Module::<T>::get_head(&oracle.table).into_iter())
-                        .map_err(|err| err.to_str());
+                    result = oracle.commit_value(&who, values, now).map_err(Error::from);
                 });
                 result?;
+
+                Self::deposit_event(RawEvent::ValueCommitted(oracle_id, who, committed));
+                Ok(())
             }
+        }
 
-            if values.0.len() != oracle.value.0.len()
+        /// Commit values only for some assets, as `(external_asset_id, value)` pairs.
+        /// Other assets of the source are left as is.
+        pub fn commit_sparse(
+            origin,
+            oracle_id: T::OracleId,
+            values: Vec<(u8, T::ValueType)>,
+        ) -> Result<(), Error>
+        {
+            let who = ensure_signed(origin)?;
+            let now = timestamp::Module::<T>::get();
+
+            if Self::prepare_commit(&who, oracle_id, now)?.is_commit_reveal()
             {
-                Err("The number of assets does not match")
+                return Err(Error::OnlyHashedCommits);
             }
-            else if !oracle.sources.contains_key(&who)
+
+            let mut result = Err(Error::OracleNotFound);
+            Oracles::<T>::mutate(oracle_id, |oracle| {
+                result = oracle.commit_sparse_value(
+                    &who,
+                    values.iter().map(|(number, value)| (*number as usize, *value)).collect(),
+                    now,
+                ).map_err(Error::from);
+            });
+            result?;
+
+            Self::deposit_event(RawEvent::ValueCommitted(oracle_id, who, values));
+            Ok(())
+        }
+
+        /// Commit the hash of SCALE encoded `(oracle_id, who, values, salt)` in the commit-reveal
+        /// mode. `values` are the values of all active assets, as in `commit`.
+        pub fn commit_hash(
+            origin,
+            oracle_id: T::OracleId,
+            hash: T::Hash,
+        ) -> Result<(), Error>
+        {
+            let who = ensure_signed(origin)?;
+            let now = timestamp::Module::<T>::get();
+
+            let oracle = Self::prepare_commit(&who, oracle_id, now)?;
+            if !oracle.is_commit_reveal()
             {
-                Err("Your account is not a source for the oracle.")
+                return Err(Error::NotCommitReveal);
             }
-            else if !oracle.period_handler.is_aggregate_time(now)
+            if !oracle.period_handler.is_commit_time(now)
             {
-                Err("No data aggregation at this time.")
+                return Err(Error::NotCommitTime);
             }
-            else
+
+            let mut result = Err(Error::OracleNotFound);
+            Oracles::<T>::mutate(oracle_id, |oracle| {
+                result = oracle.commit_hash(&who, hash, now).map_err(Error::from);
+            });
+            result?;
+
+            Self::deposit_event(RawEvent::HashCommitted(oracle_id, who, hash));
+            Ok(())
+        }
+
+        /// Reveal values committed by `commit_hash` in the reveal sub-window of the same period.
+        pub fn reveal(
+            origin,
+            oracle_id: T::OracleId,
+            values: AssetsVec<T::ValueType>,
+            salt: RawString,
+        ) -> Result<(), Error>
+        {
+            let who = ensure_signed(origin)?;
+            let now = timestamp::Module::<T>::get();
+
+            let oracle = Self::prepare_commit(&who, oracle_id, now)?;
+            if !oracle.is_commit_reveal() || !oracle.period_handler.is_reveal_time(now)
             {
-                let mut result = Err("Can't find oracle.");
-                Oracles::<T>::mutate(oracle_id, |oracle| {
-                    result = oracle.commit_value(&who, values, now).map_err(|err| err.to_str());
-                });
-                result
+                return Err(Error::NotRevealTime);
+            }
+            if values.0.len() != oracle.get_active_assets_count()
+            {
+                return Err(Error::AssetsCountMismatch);
             }
+
+            let committed = Self::get_committed_values(&oracle, &values);
+            let mut result = Err(Error::OracleNotFound);
+            Oracles::<T>::mutate(oracle_id, |oracle| {
+                result = oracle.reveal_value(oracle_id, &who, values, salt, now).map_err(Error::from);
+            });
+            result?;
+
+            Self::deposit_event(RawEvent::ValueCommitted(oracle_id, who, committed));
+            Ok(())
         }
 
         pub fn calculate(
             origin,
             oracle_id: T::OracleId,
             number: u8,
-        ) -> SimpleResult
+        ) -> Result<(), Error>
         {
             ensure_signed(origin)?;
-            let mut result = Err("Can't find oracle.");
+            Self::calculate_value(oracle_id, number, timestamp::Module::<T>::get()).map(|_| ())
+        }
 
-            let now = timestamp::Module::<T>::get();
-            Oracles::<T>::mutate(oracle_id, |oracle| {
-                if oracle.is_calculate_time(number as usize, now)
+        /// Changes the maximum of sources of the oracle and the head of its table.
+        pub fn set_sources_threshold(
+            origin,
+            oracle_id: T::OracleId,
+            sources_threshold: u8,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.set_sources_threshold(sources_threshold)?;
+
+            tablescore::Module::<T>::set_head_count(&oracle.table, sources_threshold);
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Changes the periods of the oracle. The current period ends now.
+        pub fn set_periods(
+            origin,
+            oracle_id: T::OracleId,
+            calculate_period: TimeInterval<T>,
+            aggregate_period: TimeInterval<T>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.period_handler.set_periods(timestamp::Module::<T>::get(), calculate_period, aggregate_period)
+                .map_err(|_| Error::WrongPeriods)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Enables the commit-reveal mode with the reveal sub-window at the end
+        /// of the aggregation window. Zero disables the mode.
+        pub fn set_reveal_period(
+            origin,
+            oracle_id: T::OracleId,
+            reveal_period: TimeInterval<T>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.period_handler.set_reveal_period(reveal_period).map_err(|_| Error::WrongRevealPeriod)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Sets the rule for slashing sources with deviating values, `None` disables slashing.
+        pub fn set_slash_rule(
+            origin,
+            oracle_id: T::OracleId,
+            slash_rule: Option<SlashRule>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.slash_rule = slash_rule;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Sets the rule for rewarding sources that commit on time values close to the calculated one,
+        /// `None` disables rewards. The asset can't be changed while the reward pool is not empty.
+        pub fn set_reward_rule(
+            origin,
+            oracle_id: T::OracleId,
+            reward_rule: Option<RewardRule<AssetId<T>, Balance<T>>>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            if !Self::reward_pool(oracle_id).is_zero()
+            {
+                let old_asset = oracle.reward_rule.map(|rule| rule.asset_id);
+                if reward_rule.map(|rule| rule.asset_id) != old_asset
                 {
-                    result = oracle.calculate_median(number as usize, now).map_err(|err| err.to_str()).map(|_val| ());
+                    return Err(Error::RewardPoolNotEmpty);
                 }
-                else
+            }
+            oracle.reward_rule = reward_rule;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Adds `amount` of the reward asset to the reward pool of the oracle.
+        pub fn top_up(
+            origin,
+            oracle_id: T::OracleId,
+            amount: Balance<T>,
+        ) -> Result<(), Error>
+        {
+            let who = ensure_signed(origin)?;
+            if !Oracles::<T>::exists(oracle_id)
+            {
+                return Err(Error::OracleNotFound);
+            }
+
+            let oracle = Self::oracles(oracle_id);
+            if oracle.is_closed()
+            {
+                return Err(Error::OracleClosed);
+            }
+            let rule = oracle.reward_rule.ok_or(Error::NoRewardRule)?;
+            if amount.is_zero()
+            {
+                return Err(Error::ZeroTopUp);
+            }
+
+            assets::Module::<T>::make_transfer(&rule.asset_id, &who, &Self::reward_pot(), amount)?;
+            RewardPools::<T>::mutate(oracle_id, |pool| *pool = pool.saturating_add(amount));
+
+            Self::deposit_event(RawEvent::RewardPoolToppedUp(oracle_id, who, amount));
+            Ok(())
+        }
+
+        /// Sets the commission the caller keeps from its rewards as a source in all oracles.
+        /// The rest of a reward is shared between the voters of the source in the oracle table.
+        pub fn set_commission(
+            origin,
+            commission: Permill,
+        ) -> Result<(), Error>
+        {
+            let who = ensure_signed(origin)?;
+            SourceCommissions::<T>::insert(who, commission);
+            Ok(())
+        }
+
+        pub fn rename(
+            origin,
+            oracle_id: T::OracleId,
+            name: RawString,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.name = name;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        pub fn rename_asset(
+            origin,
+            oracle_id: T::OracleId,
+            external_asset_id: u8,
+            name: RawString,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.rename_asset(external_asset_id as usize, name)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Adds the asset with the next id. The threshold is the sources threshold by default.
+        pub fn add_asset(
+            origin,
+            oracle_id: T::OracleId,
+            name: RawString,
+            threshold: Option<u8>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            let threshold = threshold.unwrap_or(oracle.get_sources_threshold());
+            oracle.add_asset(name, threshold)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Stops commits and calculations of the asset, so consumers can move from it before removal.
+        pub fn deprecate_asset(
+            origin,
+            oracle_id: T::OracleId,
+            external_asset_id: u8,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.deprecate_asset(external_asset_id as usize)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Changes the order of the assets in `commit`. `assets_id` are all asset ids of the oracle
+        /// in the new order.
+        pub fn reorder_assets(
+            origin,
+            oracle_id: T::OracleId,
+            assets_id: Vec<u8>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.reorder_assets(assets_id)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
+        }
+
+        /// Removes the deprecated asset with its values and history. Its id is not reused.
+        pub fn remove_asset(
+            origin,
+            oracle_id: T::OracleId,
+            external_asset_id: u8,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.remove_asset(external_asset_id as usize)?;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            HistoryPeriods::<T>::take((oracle_id, external_asset_id))
+                .into_iter()
+                .for_each(|period| ValuesHistory::<T>::remove((oracle_id, external_asset_id, period)));
+            Ok(())
+        }
+
+        /// Stops commits and calculations of the oracle. After `ClosingGracePeriod`
+        /// anyone can remove it with `cleanup`.
+        pub fn close(
+            origin,
+            oracle_id: T::OracleId,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            let now = timestamp::Module::<T>::get();
+            oracle.closed_at = Some(now);
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Self::deposit_event(RawEvent::OracleClosed(oracle_id, now));
+            Ok(())
+        }
+
+        /// Removes the closed oracle with its history and table, unreserving the assets of the voters.
+        pub fn cleanup(
+            origin,
+            oracle_id: T::OracleId,
+        ) -> Result<(), Error>
+        {
+            ensure_signed(origin)?;
+            if !Oracles::<T>::exists(oracle_id)
+            {
+                return Err(Error::OracleNotFound);
+            }
+
+            let oracle = Self::oracles(oracle_id);
+            match oracle.closed_at
+            {
+                None => return Err(Error::OracleNotClosed),
+                Some(closed_at) if timestamp::Module::<T>::get() < closed_at.saturating_add(T::ClosingGracePeriod::get()) =>
                 {
-                    result = Err("The calculation time has not come. Use old value.");
+                    return Err(Error::GracePeriodNotPassed);
                 }
+                _ => (),
+            }
+
+            // The pool is returned before any storage is changed, so a failed transfer
+            // leaves the oracle as it was.
+            let pool = Self::reward_pool(oracle_id);
+            if let (Some(rule), false) = (oracle.reward_rule, pool.is_zero())
+            {
+                assets::Module::<T>::make_transfer(&rule.asset_id, &Self::reward_pot(), &oracle.owner, pool)?;
+            }
+            RewardPools::<T>::remove(oracle_id);
+
+            oracle.assets_id.0.iter().for_each(|id| {
+                HistoryPeriods::<T>::take((oracle_id, *id))
+                    .into_iter()
+                    .for_each(|period| ValuesHistory::<T>::remove((oracle_id, *id, period)));
             });
+            tablescore::Module::<T>::remove_table(&oracle.table);
+            LastCalculatePeriods::<T>::remove(oracle_id);
+            SlashedSources::<T>::remove(oracle_id);
+            Oracles::<T>::remove(oracle_id);
 
-            result
+            Self::deposit_event(RawEvent::OracleRemoved(oracle_id));
+            Ok(())
+        }
+
+        pub fn transfer_ownership(
+            origin,
+            oracle_id: T::OracleId,
+            new_owner: AccountId<T>,
+        ) -> Result<(), Error>
+        {
+            let mut oracle = Self::ensure_owner_or_root(origin, oracle_id)?;
+            oracle.owner = new_owner;
+
+            Oracles::<T>::insert(oracle_id, oracle);
+            Ok(())
         }
     }
 }
@@ -116,16 +678,682 @@ decl_event!(
     pub enum Event<T>
     where
         AccountId = <T as system::Trait>::AccountId,
+        OracleId = <T as Trait>::OracleId,
+        ValueType = <T as Trait>::ValueType,
+        Moment = <T as timestamp::Trait>::Moment,
+        Balance = <T as assets::Trait>::Balance,
+        Hash = <T as system::Trait>::Hash,
     {
-        OracleCreated(AccountId),
+        /// The account created the oracle.
+        OracleCreated(OracleId, AccountId),
+        /// The source committed values of the assets, as `(external_asset_id, value)` pairs.
+        ValueCommitted(OracleId, AccountId, Vec<(u8, ValueType)>),
+        /// The source committed the hash of its values in the commit-reveal mode.
+        HashCommitted(OracleId, AccountId, Hash),
+        /// Sources of the oracle were updated from the head of its table.
+        SourcesUpdated(OracleId, Vec<AccountId>),
+        /// Value of the asset `(oracle_id, external_asset_id)` was calculated in the period.
+        ValueCalculated(OracleId, u8, ValueType, Moment),
+        /// The oracle was closed at the moment, it will be removed after the grace period.
+        OracleClosed(OracleId, Moment),
+        /// The oracle was removed and its reward pool returned to the owner.
+        OracleRemoved(OracleId),
+        /// Votes for the source were slashed by the total amount for deviating values of the period.
+        SourceSlashed(OracleId, AccountId, Balance),
+        /// The account added the amount to the reward pool of the oracle.
+        RewardPoolToppedUp(OracleId, AccountId, Balance),
+        /// The source was rewarded the amount from the reward pool of the oracle,
+        /// including the parts shared with its voters.
+        SourceRewarded(OracleId, AccountId, Balance),
+        /// The voter was paid the amount from the reward of the source it votes for.
+        VoterRewarded(OracleId, AccountId, Balance),
     }
 );
 
 impl<T: Trait> Module<T>
 {
-    fn pop_new_oracle_id() -> Result<T::OracleId, &'static str>
+    pub fn get_value(oracle_id: T::OracleId, external_asset_id: usize) -> Option<ExternalValue<T>>
+    {
+        Self::oracles(oracle_id)
+            .get_value(external_asset_id)
+            .cloned()
+    }
+
+    /// Ids and names of all oracles, removed oracles are skipped.
+    pub fn get_oracles() -> Vec<(T::OracleId, RawString)>
+    {
+        let mut oracles = Vec::new();
+        let mut oracle_id = T::OracleId::zero();
+        while oracle_id < Self::next_oracle_id()
+        {
+            if Oracles::<T>::exists(oracle_id)
+            {
+                oracles.push((oracle_id, Self::oracles(oracle_id).name));
+            }
+            oracle_id = oracle_id + One::one();
+        }
+        oracles
+    }
+
+    /// Calculated values of all assets of the oracle with their ids.
+    pub fn get_values(oracle_id: T::OracleId) -> Vec<(u8, ExternalValue<T>)>
+    {
+        let oracle = Self::oracles(oracle_id);
+        oracle
+            .assets_id
+            .0
+            .into_iter()
+            .zip(oracle.value.0.into_iter())
+            .collect()
+    }
+
+    /// Ids, names and deprecation flags of all assets of the oracle.
+    pub fn get_assets(oracle_id: T::OracleId) -> Vec<(u8, RawString, bool)>
+    {
+        let oracle = Self::oracles(oracle_id);
+        oracle
+            .assets_id
+            .0
+            .into_iter()
+            .zip(oracle.assets_name.0.into_iter())
+            .zip(oracle.assets_deprecated.0.into_iter())
+            .map(|((id, name), deprecated)| (id, name, deprecated))
+            .collect()
+    }
+
+    /// Sources that can commit in the current aggregation window. When the sources
+    /// are going to be updated by the next commit, they are taken from the table head.
+    /// No sources if the oracle doesn't exist.
+    pub fn get_current_sources(oracle_id: T::OracleId) -> Vec<AccountId<T>>
+    {
+        if !Oracles::<T>::exists(oracle_id)
+        {
+            return Vec::new();
+        }
+        let oracle = Self::oracles(oracle_id);
+        if oracle
+            .period_handler
+            .is_sources_update_needed(timestamp::Module::<T>::get())
+        {
+            tablescore::Module::<T>::get_head(&oracle.table)
+        }
+        else
+        {
+            oracle.sources.keys().cloned().collect()
+        }
+    }
+
+    pub fn get_current_period(oracle_id: T::OracleId) -> Option<Moment<T>>
+    {
+        Self::get_open_oracle(oracle_id)
+            .map(|oracle| oracle.period_handler.get_period(timestamp::Module::<T>::get()))
+    }
+
+    pub fn is_aggregate_time(oracle_id: T::OracleId) -> bool
+    {
+        Self::get_open_oracle(oracle_id).map_or(false, |oracle| {
+            oracle
+                .period_handler
+                .is_aggregate_time(timestamp::Module::<T>::get())
+        })
+    }
+
+    /// Time until the next aggregation window of the oracle, zero in the window.
+    pub fn get_time_to_aggregate(oracle_id: T::OracleId) -> Option<Moment<T>>
+    {
+        Self::get_open_oracle(oracle_id).map(|oracle| {
+            oracle
+                .period_handler
+                .get_time_to_aggregate(timestamp::Module::<T>::get())
+        })
+    }
+
+    /// Whether the account can commit to the oracle now. In the commit-reveal mode
+    /// it is the time to commit the hash or to reveal the values.
+    pub fn can_commit(oracle_id: T::OracleId, account: &AccountId<T>) -> bool
+    {
+        Self::is_aggregate_time(oracle_id)
+            && Self::get_current_sources(oracle_id).contains(account)
+    }
+
+    fn get_open_oracle(oracle_id: T::OracleId) -> Option<Oracle<T>>
+    {
+        if Oracles::<T>::exists(oracle_id)
+        {
+            Some(Self::oracles(oracle_id)).filter(|oracle| !oracle.is_closed())
+        }
+        else
+        {
+            None
+        }
+    }
+
+    /// Pairs of `(external_asset_id, value)` from the values of all active assets.
+    fn get_committed_values(
+        oracle: &Oracle<T>,
+        values: &AssetsVec<T::ValueType>,
+    ) -> Vec<(u8, T::ValueType)>
+    {
+        oracle
+            .get_active_assets_id()
+            .into_iter()
+            .zip(values.0.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the oracle if `origin` is its owner or root.
+    fn ensure_owner_or_root(
+        origin: T::Origin,
+        oracle_id: T::OracleId,
+    ) -> Result<Oracle<T>, Error>
+    {
+        if !Oracles::<T>::exists(oracle_id)
+        {
+            return Err(Error::OracleNotFound);
+        }
+
+        let oracle = Self::oracles(oracle_id);
+        if !(ensure_root(origin.clone()).is_ok() || ensure_signed(origin)? == oracle.owner)
+        {
+            Err(Error::NotOwner)
+        }
+        else if oracle.is_closed()
+        {
+            Err(Error::OracleClosed)
+        }
+        else
+        {
+            Ok(oracle)
+        }
+    }
+
+    /// Calculates the asset value, stores it in the history and deposits `ValueCalculated`.
+    fn calculate_value(
+        oracle_id: T::OracleId,
+        number: u8,
+        now: Moment<T>,
+    ) -> Result<T::ValueType, Error>
+    {
+        if !Oracles::<T>::exists(oracle_id)
+        {
+            return Err(Error::OracleNotFound);
+        }
+
+        let mut result = Err(Error::OracleNotFound);
+        let mut calculated = None;
+        Oracles::<T>::mutate(oracle_id, |oracle| {
+            if oracle.is_closed()
+            {
+                result = Err(Error::OracleClosed);
+            }
+            else if oracle.is_calculate_time(number as usize, now)
+            {
+                result = oracle.calculate_median(number as usize, now).map_err(Error::from);
+                if result.is_ok()
+                {
+                    calculated = oracle
+                        .get_value(number as usize)
+                        .map(|external| (oracle.period_handler.get_period(now), external.clone()));
+                }
+            }
+            else
+            {
+                result = Err(Error::NotCalculateTime);
+            }
+        });
+
+        if let (Ok(value), Some((period, external))) = (result.clone(), calculated)
+        {
+            Self::store_history(oracle_id, number, period, external);
+            Self::deposit_event(RawEvent::ValueCalculated(oracle_id, number, value, period));
+            Self::slash_deviating_sources(oracle_id, number, value, now);
+            Self::reward_sources(oracle_id, number, value, now);
+        }
+
+        result
+    }
+
+    /// Slashes the votes for sources whose values of the asset deviate from `calculated`.
+    /// All voters for such a source lose the same part of their votes. Sources already
+    /// slashed in the period are skipped.
+    fn slash_deviating_sources(
+        oracle_id: T::OracleId,
+        number: u8,
+        calculated: T::ValueType,
+        now: Moment<T>,
+    )
+    {
+        let oracle = Self::oracles(oracle_id);
+        if let Some(rule) = oracle.slash_rule
+        {
+            let period = oracle.period_handler.get_period(now);
+            let (slashed_period, mut slashed) = Self::slashed_sources(oracle_id);
+            if slashed_period != period
+            {
+                slashed.clear();
+            }
+
+            let destination = T::SlashDestination::get();
+            for source in oracle.get_deviating_sources(number as usize, calculated, now)
+            {
+                if slashed.contains(&source)
+                {
+                    continue;
+                }
+
+                let amount = tablescore::Module::<T>::slash_target(
+                    &oracle.table,
+                    &source,
+                    rule.slash,
+                    destination.as_ref(),
+                );
+                slashed.push(source.clone());
+                Self::deposit_event(RawEvent::SourceSlashed(oracle_id, source, amount));
+            }
+            SlashedSources::<T>::insert(oracle_id, (period, slashed));
+        }
+    }
+
+    /// Pays the reward of the period for the asset from the reward pool, equally to every source
+    /// that committed the asset in the aggregation window a value close to `calculated`.
+    fn reward_sources(
+        oracle_id: T::OracleId,
+        number: u8,
+        calculated: T::ValueType,
+        now: Moment<T>,
+    )
+    {
+        let oracle = Self::oracles(oracle_id);
+        if let Some(rule) = oracle.reward_rule
+        {
+            let sources = oracle.get_rewarded_sources(number as usize, calculated, now);
+            let reward = rule.get_source_reward(
+                oracle.get_active_assets_count(),
+                sources.len(),
+                Self::reward_pool(oracle_id),
+            );
+            if reward.is_zero()
+            {
+                return;
+            }
+
+            sources.into_iter().for_each(|source| {
+                let paid = Self::pay_source_reward(oracle_id, &rule.asset_id, &source, reward);
+                if !paid.is_zero()
+                {
+                    RewardPools::<T>::mutate(oracle_id, |pool| *pool = pool.saturating_sub(paid));
+                    Self::deposit_event(RawEvent::SourceRewarded(oracle_id, source, paid));
+                }
+            });
+        }
+    }
+
+    /// Pays the source its commission and shares the rest of the reward between its voters.
+    /// Failed transfers are skipped and stay in the pool. Returns the amount actually paid.
+    fn pay_source_reward(
+        oracle_id: T::OracleId,
+        asset_id: &AssetId<T>,
+        source: &AccountId<T>,
+        reward: Balance<T>,
+    ) -> Balance<T>
+    {
+        let pot = Self::reward_pot();
+        let (source_part, shares) = split_reward(
+            reward,
+            Self::source_commission(source),
+            tablescore::Module::<T>::get_backers(&Self::oracles(oracle_id).table, source),
+        );
+
+        let mut paid = Zero::zero();
+        if !source_part.is_zero()
+            && assets::Module::<T>::make_transfer(asset_id, &pot, source, source_part).is_ok()
+        {
+            paid = source_part;
+        }
+        for (voter, share) in shares.into_iter()
+        {
+            if assets::Module::<T>::make_transfer(asset_id, &pot, &voter, share).is_ok()
+            {
+                paid = paid.saturating_add(share);
+                Self::deposit_event(RawEvent::VoterRewarded(oracle_id, voter, share));
+            }
+        }
+        paid
+    }
+
+    /// Account that holds the assets of all reward pools.
+    pub fn reward_pot() -> AccountId<T>
+    {
+        MODULE_ID.into_account()
+    }
+
+    /// Calculates all assets that are due in at most `MaxOraclesPerBlock` oracles.
+    /// Oracles that don't fit in this block are checked in the next ones.
+    /// Every oracle is calculated at most once per period.
+    fn calculate_oracles(now: Moment<T>)
+    {
+        let oracles_count = Self::next_oracle_id();
+        if oracles_count.is_zero()
+        {
+            return;
+        }
+
+        let next = |oracle_id: T::OracleId| {
+            let oracle_id = oracle_id + One::one();
+            if oracle_id < oracles_count
+            {
+                oracle_id
+            }
+            else
+            {
+                Zero::zero()
+            }
+        };
+
+        let first = if Self::next_calculated_oracle() < oracles_count
+        {
+            Self::next_calculated_oracle()
+        }
+        else
+        {
+            Zero::zero()
+        };
+
+        let mut oracle_id = first;
+        for _ in 0..T::MaxOraclesPerBlock::get()
+        {
+            if Oracles::<T>::exists(oracle_id)
+            {
+                let oracle = Self::oracles(oracle_id);
+                let period = oracle.period_handler.get_period(now);
+                let due: Vec<u8> = oracle
+                    .assets_id
+                    .0
+                    .iter()
+                    .filter(|id| oracle.is_calculate_time(**id as usize, now))
+                    .cloned()
+                    .collect();
+
+                if !due.is_empty() && Self::last_calculate_period(oracle_id) != Some(period)
+                {
+                    LastCalculatePeriods::<T>::insert(oracle_id, period);
+                    due.into_iter().for_each(|id| {
+                        let _ = Self::calculate_value(oracle_id, id, now);
+                    });
+                }
+            }
+
+            oracle_id = next(oracle_id);
+            if oracle_id == first
+            {
+                break;
+            }
+        }
+
+        NextCalculatedOracle::<T>::put(oracle_id);
+    }
+
+    /// Updates sources of the oracle if needed and checks that `who` can commit now.
+    fn prepare_commit(
+        who: &AccountId<T>,
+        oracle_id: T::OracleId,
+        now: Moment<T>,
+    ) -> Result<Oracle<T>, Error>
+    {
+        if !Oracles::<T>::exists(oracle_id)
+        {
+            return Err(Error::OracleNotFound);
+        }
+
+        let oracle = Oracles::<T>::get(oracle_id);
+        if oracle.is_closed()
+        {
+            return Err(Error::OracleClosed);
+        }
+
+        if oracle.period_handler.is_sources_update_needed(now)
+        {
+            let mut result = Err(Error::OracleNotFound);
+            Oracles::<T>::mutate(oracle_id, |oracle| {
+                result = oracle
+                    .update_accounts(
+                        tablescore::Module::<T>::get_head_with_stake(&oracle.table).into_iter(),
+                    )
+                    .map_err(Error::from);
+            });
+            result?;
+
+            let sources: Vec<AccountId<T>> = Self::oracles(oracle_id).sources.keys().cloned().collect();
+            if !oracle.sources.keys().eq(sources.iter())
+            {
+                Self::deposit_event(RawEvent::SourcesUpdated(oracle_id, sources));
+            }
+        }
+
+        let oracle = Oracles::<T>::get(oracle_id);
+        if !oracle.sources.contains_key(who)
+        {
+            Err(Error::NotSource)
+        }
+        else if !oracle.period_handler.is_aggregate_time(now)
+        {
+            Err(Error::NotAggregateTime)
+        }
+        else if oracle.is_committed_at(who, now)
+        {
+            Err(Error::AlreadyCommitted)
+        }
+        else
+        {
+            Ok(oracle)
+        }
+    }
+
+    /// Value of the asset that was actual at `moment`, if it is still kept in the history.
+    pub fn get_value_at(
+        oracle_id: T::OracleId,
+        external_asset_id: u8,
+        moment: Moment<T>,
+    ) -> Option<ExternalValue<T>>
+    {
+        Self::history_periods((oracle_id, external_asset_id))
+            .into_iter()
+            .rev()
+            .filter_map(|period| Self::values_history((oracle_id, external_asset_id, period)))
+            .find(|value| value.last_changed.map_or(false, |changed| changed <= moment))
+    }
+
+    /// Time-weighted average of the asset values in `[from, to)`. Every value from the history
+    /// is weighted by the time it was actual. Time without a known value is skipped.
+    pub fn get_twap(
+        oracle_id: T::OracleId,
+        external_asset_id: u8,
+        from: Moment<T>,
+        to: Moment<T>,
+    ) -> Option<T::ValueType>
+    {
+        let history: Vec<(Moment<T>, T::ValueType)> = Self::history_periods((oracle_id, external_asset_id))
+            .into_iter()
+            .filter_map(|period| Self::values_history((oracle_id, external_asset_id, period)))
+            .filter_map(|external| external.last_changed.and_then(|changed| external.value.map(|value| (changed, value))))
+            .collect();
+
+        let weighted: Vec<(T::ValueType, T::ValueType)> = history
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (changed, value))| {
+                let begin = max(*changed, from);
+                let end = history
+                    .get(index + 1)
+                    .map_or(to, |(next_changed, _)| min(*next_changed, to));
+
+                if begin < end
+                {
+                    Some((*value, Self::duration_to_value(end - begin)))
+                }
+                else
+                {
+                    None
+                }
+            })
+            .collect();
+
+        get_weighted_mean(&weighted)
+    }
+
+    /// Time-weighted average of the asset values over the last `periods` complete periods.
+    /// `None` if the oracle doesn't exist.
+    pub fn get_twap_by_periods(
+        oracle_id: T::OracleId,
+        external_asset_id: u8,
+        periods: u32,
+    ) -> Option<T::ValueType>
+    {
+        if !Oracles::<T>::exists(oracle_id)
+        {
+            return None;
+        }
+
+        let period_handler = Self::oracles(oracle_id).period_handler;
+        let current = period_handler.get_period(timestamp::Module::<T>::get());
+
+        Self::get_twap(
+            oracle_id,
+            external_asset_id,
+            period_handler.get_period_begin(current.saturating_sub(Moment::<T>::from(periods))),
+            period_handler.get_period_begin(current),
+        )
+    }
+
+    fn duration_to_value(duration: Moment<T>) -> T::ValueType
+    {
+        let duration: u128 = duration.try_into().unwrap_or_else(|_| u128::max_value());
+        T::ValueType::try_from(duration).unwrap_or_else(|_| Bounded::max_value())
+    }
+
+    fn store_history(
+        oracle_id: T::OracleId,
+        external_asset_id: u8,
+        period: Moment<T>,
+        value: ExternalValue<T>,
+    )
+    {
+        ValuesHistory::<T>::insert((oracle_id, external_asset_id, period), value);
+
+        HistoryPeriods::<T>::mutate((oracle_id, external_asset_id), |periods| {
+            periods.push(period);
+
+            let depth = T::HistoryDepth::get() as usize;
+            if periods.len() > depth
+            {
+                periods
+                    .drain(..periods.len() - depth)
+                    .for_each(|old| ValuesHistory::<T>::remove((oracle_id, external_asset_id, old)));
+            }
+        });
+    }
+
+    /// Fetches the values of the assets from the endpoints in the offchain storage of
+    /// the node and commits them by the local keys that are current sources. Runs in
+    /// the commit time, the values are committed once per period.
+    pub fn commit_offchain_values(now: Moment<T>)
+    {
+        let keys: Vec<_> = T::AuthorityId::all()
+            .into_iter()
+            .map(|key| {
+                let public: PublicOf<T, <T as Trait>::Call, T::SubmitTransaction> = key.into();
+                (public.clone().into_account(), public)
+            })
+            .collect();
+        if keys.is_empty()
+        {
+            return;
+        }
+
+        for (oracle_id, _) in Self::get_oracles()
+        {
+            let oracle = match Self::get_open_oracle(oracle_id)
+            {
+                Some(oracle)
+                    if !oracle.is_commit_reveal() && oracle.period_handler.is_commit_time(now) =>
+                {
+                    oracle
+                }
+                _ => continue,
+            };
+
+            let period = oracle.period_handler.get_period(now).encode();
+            let sources = Self::get_current_sources(oracle_id);
+            let signers: Vec<_> = keys
+                .iter()
+                .filter(|(account, _)| {
+                    sources.contains(account)
+                        && !offchain::is_committed(&(oracle_id, account).encode(), &period)
+                })
+                .collect();
+            if signers.is_empty()
+            {
+                continue;
+            }
+
+            let call = match Self::fetch_offchain_values(oracle_id, &oracle)
+            {
+                Some(call) => call,
+                None => continue,
+            };
+            for (account, public) in signers
+            {
+                if T::SubmitTransaction::sign_and_submit(call.clone(), public.clone()).is_ok()
+                {
+                    offchain::mark_committed(&(oracle_id, account).encode(), &period);
+                }
+                else
+                {
+                    sr_primitives::print("Failed to submit the commit of the offchain worker.");
+                }
+            }
+        }
+    }
+
+    /// `commit` if the values of all active assets are fetched, `commit_sparse` if only
+    /// some of them, `None` if nothing is fetched.
+    fn fetch_offchain_values(oracle_id: T::OracleId, oracle: &Oracle<T>) -> Option<Call<T>>
+    {
+        let active = oracle.get_active_assets_id();
+        let endpoints: Vec<_> = offchain::get_endpoints(&oracle_id.encode())
+            .into_iter()
+            .filter(|endpoint| active.contains(&endpoint.asset_id))
+            .collect();
+        let values: BTreeMap<u8, T::ValueType> = endpoints
+            .iter()
+            .zip(offchain::fetch_all(&endpoints))
+            .filter_map(|(endpoint, value)| {
+                let value = T::ValueType::try_from(value?).ok()?;
+                Some((endpoint.asset_id, value))
+            })
+            .collect();
+
+        if values.is_empty()
+        {
+            None
+        }
+        else if active.iter().all(|id| values.contains_key(id))
+        {
+            let values = active
+                .iter()
+                .filter_map(|id| values.get(id).cloned())
+                .collect();
+            Some(Call::commit(oracle_id, AssetsVec(values)))
+        }
+        else
+        {
+            Some(Call::commit_sparse(oracle_id, values.into_iter().collect()))
+        }
+    }
+
+    fn pop_new_oracle_id() -> Result<T::OracleId, Error>
     {
-        let mut result = Err("Unknown error");
+        let mut result = Err(Error::OracleIdOverflow);
 
         OracleIdSequnce::<T>::mutate(|id| match id.checked_add(&One::one())
         {
@@ -136,7 +1364,7 @@ impl<T: Trait> Module<T>
             }
             None =>
             {
-                result = Err("T::TableId overflow. Can't get next id.");
+                result = Err(Error::OracleIdOverflow);
             }
         });
 
@@ -2,7 +2,9 @@ pub use crate::tablescore;
 use codec::{Decode, Encode};
 use rstd::prelude::*;
 use sr_primitives::traits::{Member, SimpleArithmetic};
-use support::Parameter;
+use sr_primitives::RuntimeAppPublic;
+use support::{traits::Get, Parameter};
+use system::offchain::{PublicOf, SubmitSignedTransaction};
 
 pub type Balance<T> = <T as assets::Trait>::Balance;
 pub type AssetId<T> = <T as assets::Trait>::AssetId;
@@ -13,10 +15,32 @@ pub type RawString = Vec<u8>;
 pub trait Trait:
     assets::Trait + timestamp::Trait + tablescore::Trait<TargetType = AccountId<Self>>
 {
-    type Event: Into<<Self as system::Trait>::Event>;
+    type Event: From<crate::module::Event<Self>> + Into<<Self as system::Trait>::Event>;
     type OracleId: Parameter + Member + SimpleArithmetic + Default + Copy;
 
     type ValueType: Member + Parameter + SimpleArithmetic + Default + Copy;
+
+    /// How many calculated values of each asset are kept in the history.
+    type HistoryDepth: Get<u32>;
+
+    /// How many oracles are checked for automatic calculation in one block.
+    type MaxOraclesPerBlock: Get<u32>;
+
+    /// Time after closing an oracle before its storage can be removed.
+    type ClosingGracePeriod: Get<<Self as timestamp::Trait>::Moment>;
+
+    /// Account that receives slashed stakes of sources, `None` to burn them.
+    type SlashDestination: Get<Option<AccountId<Self>>>;
+
+    /// Call of the runtime to submit the commits of the offchain worker.
+    type Call: From<crate::module::Call<Self>>;
+
+    /// Signs and submits the commits of the offchain worker.
+    type SubmitTransaction: SubmitSignedTransaction<Self, <Self as Trait>::Call>;
+
+    /// Keys of the offchain worker, the account of a key commits as a source.
+    type AuthorityId: RuntimeAppPublic
+        + Into<PublicOf<Self, <Self as Trait>::Call, Self::SubmitTransaction>>;
 }
 
 pub type TableId<T> = <T as tablescore::Trait>::TableId;
@@ -0,0 +1,349 @@
+//! Values of the assets for the offchain worker, fetched from HTTP endpoints.
+
+use codec::{Decode, Encode};
+use primitives::offchain::{Duration, StorageKind};
+use rstd::prelude::*;
+use sr_primitives::offchain::http;
+
+/// Prefix of the offchain storage keys of the endpoints. It is followed by the SCALE
+/// encoded oracle id, the value is a SCALE encoded `Vec<Endpoint>`.
+pub const ENDPOINTS_KEY: &[u8] = b"dpos-oracle/endpoints/";
+/// Prefix of the offchain storage keys of the last periods committed by the sources.
+const COMMITTED_KEY: &[u8] = b"dpos-oracle/committed/";
+/// Time to wait for the responses of all endpoints, in milliseconds.
+const FETCH_TIMEOUT: u64 = 3_000;
+
+/// Where the offchain worker fetches the value of an asset.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Endpoint
+{
+    pub asset_id: u8,
+    /// URL of the GET request.
+    pub url: Vec<u8>,
+    /// Keys of the JSON objects and indices of the arrays on the way to the value,
+    /// empty if the whole response is the value.
+    pub path: Vec<Vec<u8>>,
+    /// The value is multiplied by `10^decimals`, extra fraction digits are truncated.
+    pub decimals: u8,
+}
+
+/// Endpoints of the oracle from the offchain storage of the node.
+pub fn get_endpoints(oracle_id: &[u8]) -> Vec<Endpoint>
+{
+    let key = [ENDPOINTS_KEY, oracle_id].concat();
+    runtime_io::offchain::local_storage_get(StorageKind::PERSISTENT, &key)
+        .and_then(|endpoints| Decode::decode(&mut &endpoints[..]).ok())
+        .unwrap_or_default()
+}
+
+/// Whether the period is the last one committed by the source of the oracle.
+pub fn is_committed(source: &[u8], period: &[u8]) -> bool
+{
+    let key = [COMMITTED_KEY, source].concat();
+    runtime_io::offchain::local_storage_get(StorageKind::PERSISTENT, &key)
+        .map_or(false, |last| last == period)
+}
+
+/// Stores the period as the last one committed by the source of the oracle.
+pub fn mark_committed(source: &[u8], period: &[u8])
+{
+    let key = [COMMITTED_KEY, source].concat();
+    runtime_io::offchain::local_storage_set(StorageKind::PERSISTENT, &key, period);
+}
+
+/// Values of the endpoints, `None` if the request fails or the value can't be parsed.
+/// All requests are sent at once and share one deadline.
+pub fn fetch_all(endpoints: &[Endpoint]) -> Vec<Option<u128>>
+{
+    let deadline = runtime_io::offchain::timestamp().add(Duration::from_millis(FETCH_TIMEOUT));
+    let (indices, requests): (Vec<usize>, Vec<http::PendingRequest>) = endpoints
+        .iter()
+        .enumerate()
+        .filter_map(|(index, endpoint)| {
+            let url = rstd::str::from_utf8(&endpoint.url).ok()?;
+            let request = http::Request::get(url).deadline(deadline).send().ok()?;
+            Some((index, request))
+        })
+        .unzip();
+
+    let mut values = vec![None; endpoints.len()];
+    for (index, response) in indices
+        .into_iter()
+        .zip(http::PendingRequest::wait_all(requests, Some(deadline)))
+    {
+        if let Ok(Ok(response)) = response
+        {
+            if response.code == 200
+            {
+                let endpoint = &endpoints[index];
+                let body: Vec<u8> = response.body().collect();
+                values[index] = parse_value(&body, &endpoint.path, endpoint.decimals);
+            }
+        }
+    }
+    values
+}
+
+/// Parses the number at `path` of the JSON `body` as in `Endpoint`.
+/// The number can also be in a JSON string.
+pub fn parse_value(body: &[u8], path: &[Vec<u8>], decimals: u8) -> Option<u128>
+{
+    let mut parser = JsonParser { data: body, pos: 0 };
+    for key in path
+    {
+        parser.enter(key)?;
+    }
+    parse_decimal(parser.scalar()?, decimals)
+}
+
+/// Parses a non-negative decimal number multiplied by `10^decimals`.
+pub fn parse_decimal(number: &[u8], decimals: u8) -> Option<u128>
+{
+    let mut parts = number.splitn(2, |c| *c == b'.');
+    let integer = parts.next()?;
+    let fraction = parts.next().unwrap_or(&[]);
+    if integer.is_empty()
+        || !integer
+            .iter()
+            .chain(fraction.iter())
+            .all(u8::is_ascii_digit)
+    {
+        return None;
+    }
+
+    integer
+        .iter()
+        .chain(
+            fraction
+                .iter()
+                .chain(rstd::iter::repeat(&b'0'))
+                .take(decimals as usize),
+        )
+        .try_fold(0u128, |value, digit| {
+            value.checked_mul(10)?.checked_add((digit - b'0') as u128)
+        })
+}
+
+/// Reads only the parts of a JSON document on the way to the value.
+struct JsonParser<'a>
+{
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a>
+{
+    fn peek(&mut self) -> Option<u8>
+    {
+        while self
+            .data
+            .get(self.pos)
+            .map_or(false, |c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        self.data.get(self.pos).cloned()
+    }
+
+    fn consume(&mut self, expected: u8) -> Option<()>
+    {
+        if self.peek()? == expected
+        {
+            self.pos += 1;
+            Some(())
+        }
+        else
+        {
+            None
+        }
+    }
+
+    /// Contents of a string, escape sequences are left as is.
+    fn string(&mut self) -> Option<&'a [u8]>
+    {
+        self.consume(b'"')?;
+        let start = self.pos;
+        loop
+        {
+            match self.data.get(self.pos)?
+            {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        Some(&self.data[start..self.pos - 1])
+    }
+
+    /// Contents of a string or a number, `true`, `false` or `null`.
+    fn scalar(&mut self) -> Option<&'a [u8]>
+    {
+        if self.peek()? == b'"'
+        {
+            return self.string();
+        }
+
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .map_or(false, |c| !b",:{}[] \t\r\n\"".contains(c))
+        {
+            self.pos += 1;
+        }
+        Some(&self.data[start..self.pos]).filter(|scalar| !scalar.is_empty())
+    }
+
+    fn skip_value(&mut self) -> Option<()>
+    {
+        match self.peek()?
+        {
+            b'{' =>
+            {
+                self.pos += 1;
+                if self.peek()? != b'}'
+                {
+                    loop
+                    {
+                        self.string()?;
+                        self.consume(b':')?;
+                        self.skip_value()?;
+                        if self.peek()? != b','
+                        {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.consume(b'}')
+            }
+            b'[' =>
+            {
+                self.pos += 1;
+                if self.peek()? != b']'
+                {
+                    loop
+                    {
+                        self.skip_value()?;
+                        if self.peek()? != b','
+                        {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.consume(b']')
+            }
+            _ => self.scalar().map(|_| ()),
+        }
+    }
+
+    /// Moves to the value of the `key` member of an object, or the element of an array
+    /// at the index in `key`.
+    fn enter(&mut self, key: &[u8]) -> Option<()>
+    {
+        match self.peek()?
+        {
+            b'{' =>
+            {
+                self.pos += 1;
+                loop
+                {
+                    let found = self.string()? == key;
+                    self.consume(b':')?;
+                    if found
+                    {
+                        return Some(());
+                    }
+                    self.skip_value()?;
+                    self.consume(b',')?;
+                }
+            }
+            b'[' =>
+            {
+                self.pos += 1;
+                for _ in 0..parse_decimal(key, 0)?
+                {
+                    self.skip_value()?;
+                    self.consume(b',')?;
+                }
+                Some(())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{parse_decimal, parse_value};
+
+    fn path(keys: &[&str]) -> Vec<Vec<u8>>
+    {
+        keys.iter().map(|key| key.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn decimal()
+    {
+        assert_eq!(parse_decimal(b"123", 0), Some(123));
+        assert_eq!(parse_decimal(b"123", 2), Some(12300));
+        assert_eq!(parse_decimal(b"123.4", 2), Some(12340));
+        assert_eq!(parse_decimal(b"123.456", 2), Some(12345));
+        assert_eq!(parse_decimal(b"0.01", 2), Some(1));
+        assert_eq!(parse_decimal(b"", 0), None);
+        assert_eq!(parse_decimal(b".5", 1), None);
+        assert_eq!(parse_decimal(b"-1", 0), None);
+        assert_eq!(parse_decimal(b"1e5", 0), None);
+        assert_eq!(
+            parse_decimal(b"340282366920938463463374607431768211456", 0),
+            None
+        );
+    }
+
+    #[test]
+    fn value()
+    {
+        let body = br#"{
+            "time": {"updated": "Oct 17, 2019", "codes": [1, {"a": null}]},
+            "bpi": {
+                "USD": {"code": "USD", "rate": "8,010.1", "rate_float": 8010.1234},
+                "EUR": {"code": "EUR", "rate_float": 7200.5}
+            },
+            "prices": [[1571270400, 8010.5], [1571356800, 8100]]
+        }"#;
+
+        assert_eq!(
+            parse_value(body, &path(&["bpi", "USD", "rate_float"]), 2),
+            Some(801012)
+        );
+        assert_eq!(
+            parse_value(body, &path(&["bpi", "EUR", "rate_float"]), 0),
+            Some(7200)
+        );
+        assert_eq!(
+            parse_value(body, &path(&["prices", "1", "1"]), 1),
+            Some(81000)
+        );
+        assert_eq!(parse_value(body, &path(&["bpi", "USD", "rate"]), 0), None);
+        assert_eq!(
+            parse_value(body, &path(&["bpi", "GBP", "rate_float"]), 0),
+            None
+        );
+        assert_eq!(parse_value(body, &path(&["prices", "2", "1"]), 0), None);
+        assert_eq!(parse_value(body, &path(&["time"]), 0), None);
+
+        assert_eq!(parse_value(b" 42 ", &[], 0), Some(42));
+        assert_eq!(
+            parse_value(br#"{"price": "10.5"}"#, &path(&["price"]), 2),
+            Some(1050)
+        );
+        assert_eq!(
+            parse_value(br#"{"a\"b": 1, "c": 2}"#, &path(&["c"]), 0),
+            Some(2)
+        );
+    }
+}
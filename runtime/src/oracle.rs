@@ -2,31 +2,59 @@ use rstd::prelude::*;
 
 use codec::{Decode, Encode};
 use rstd::cmp::{Ord, Ordering};
-use rstd::collections::btree_map::BTreeMap;
-use sr_primitives::traits::One;
+use rstd::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
+use sr_primitives::traits::{Hash, Zero};
 
 pub use crate::external_value::*;
 pub use crate::module_trait::*;
-pub use crate::period_handler::PeriodHandler;
+pub use crate::period_handler::{PeriodHandler, StalenessPolicy};
 
-use crate::median::{get_median, Median};
+pub use crate::aggregator::{Aggregator, AggregatorType};
+pub use crate::outlier::OutlierFilter;
+pub use crate::reward::RewardRule;
+pub use crate::slashing::SlashRule;
 
 #[derive(Encode, Decode, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
 pub struct Oracle<T: Trait>
 {
     pub name: RawString,
+    /// Account that can change the oracle parameters.
+    pub owner: AccountId<T>,
     pub table: TableId<T>,
 
+    /// Maximum of sources and default quorum of assets.
     sources_threshold: u8,
     pub period_handler: PeriodHandler<T::Moment>,
+    pub aggregator: AggregatorType,
+    pub outlier_filter: OutlierFilter,
+    pub staleness: StalenessPolicy,
+    pub slash_rule: Option<SlashRule>,
+    pub reward_rule: Option<RewardRule<AssetId<T>, Balance<T>>>,
 
+    /// Stable ids of the assets. Other assets vectors, including the values of the sources,
+    /// are in the same order. Ids of removed assets are not reused.
+    pub assets_id: AssetsVec<u8>,
     pub assets_name: AssetsVec<RawString>,
+    /// Minimum of committed values for calculation of each asset.
+    pub assets_threshold: AssetsVec<u8>,
+    /// Deprecated assets are not committed and calculated anymore and can be removed.
+    pub assets_deprecated: AssetsVec<bool>,
+    next_asset_id: Option<u8>,
 
     pub sources: BTreeMap<AccountId<T>, AssetsVec<ExternalValue<T>>>,
+    pub sources_stake: BTreeMap<AccountId<T>, Balance<T>>,
+    /// Hashes of `(values, salt)` committed in the commit-reveal mode, with the commit moment.
+    pub commitments: BTreeMap<AccountId<T>, (T::Hash, Moment<T>)>,
     pub value: AssetsVec<ExternalValue<T>>,
+    /// Sources excluded by the outlier filter at the last calculation of each asset.
+    pub excluded_sources: AssetsVec<Vec<AccountId<T>>>,
+
+    /// Moment when the oracle was closed. Closed oracle is not committed and calculated.
+    pub closed_at: Option<Moment<T>>,
 }
 
+/// Errors of the oracle logic with their details. Extrinsics return them as the module `Error`.
 #[cfg_attr(feature = "std", derive(Debug, PartialEq))]
 pub enum OracleError
 {
@@ -35,21 +63,11 @@ pub enum OracleError
     WrongAssetId(usize),
     TooFewAccounts(usize, usize),
     AccountAccess,
-}
-
-impl OracleError
-{
-    pub fn to_str(&self) -> &'static str
-    {
-        match self
-        {
-            Self::WrongAssetId(_) => "Wrong asset id.",
-            Self::TooFewAccounts(_, _) => "There are fewer accounts than the minimum.",
-            Self::CalculationError => "Unknown calculation error.",
-            Self::AccountAccess => "Your account does not have access to send.",
-            Self::TooFewCommitedValue(_, _) => "There are fewer actual values than the minimum.",
-        }
-    }
+    WrongThreshold(u8),
+    WrongReveal,
+    DeprecatedAsset(usize),
+    NotDeprecatedAsset(usize),
+    TooManyAssets,
 }
 
 impl<T: Trait> Default for Oracle<T>
@@ -58,12 +76,26 @@ impl<T: Trait> Default for Oracle<T>
     {
         Oracle {
             name: Vec::new(),
+            owner: AccountId::<T>::default(),
             table: TableId::<T>::default(),
             sources_threshold: u8::default(),
+            aggregator: AggregatorType::default(),
+            outlier_filter: OutlierFilter::default(),
+            staleness: StalenessPolicy::default(),
+            slash_rule: None,
+            reward_rule: None,
             sources: BTreeMap::default(),
+            sources_stake: BTreeMap::default(),
+            commitments: BTreeMap::default(),
+            assets_id: AssetsVec::default(),
             assets_name: AssetsVec::default(),
+            assets_threshold: AssetsVec::default(),
+            assets_deprecated: AssetsVec::default(),
+            next_asset_id: Some(0),
             value: AssetsVec::default(),
+            excluded_sources: AssetsVec::default(),
             period_handler: PeriodHandler::default(),
+            closed_at: None,
         }
     }
 }
@@ -72,33 +104,185 @@ impl<T: Trait> Oracle<T>
 {
     pub fn new(
         name: RawString,
+        owner: AccountId<T>,
         table: TableId<T>,
         period_handler: PeriodHandler<T::Moment>,
         sources_threshold: u8,
+        aggregator: AggregatorType,
+        outlier_filter: OutlierFilter,
+        staleness: StalenessPolicy,
         assets: AssetsVec<RawString>,
     ) -> Oracle<T>
     {
         Oracle {
             name,
+            owner,
             table,
             sources_threshold,
             period_handler,
+            aggregator,
+            outlier_filter,
+            staleness,
+            slash_rule: None,
+            reward_rule: None,
             sources: BTreeMap::new(),
+            sources_stake: BTreeMap::new(),
+            commitments: BTreeMap::new(),
             value: AssetsVec {
                 0: assets.0.iter().map(|_| ExternalValue::<T>::new()).collect(),
             },
+            excluded_sources: AssetsVec {
+                0: assets.0.iter().map(|_| Vec::new()).collect(),
+            },
+            assets_id: AssetsVec {
+                0: (0..assets.0.len()).map(|id| id as u8).collect(),
+            },
             assets_name: AssetsVec {
                 0: assets.0.iter().cloned().collect(),
             },
+            assets_threshold: AssetsVec {
+                0: assets.0.iter().map(|_| sources_threshold).collect(),
+            },
+            assets_deprecated: AssetsVec {
+                0: assets.0.iter().map(|_| false).collect(),
+            },
+            closed_at: None,
+            next_asset_id: if assets.0.len() <= u8::max_value() as usize
+            {
+                Some(assets.0.len() as u8)
+            }
+            else
+            {
+                None
+            },
+        }
+    }
+
+    /// Position of the asset in the assets vectors.
+    fn get_asset_index(&self, external_asset_id: usize) -> Result<usize, OracleError>
+    {
+        self.assets_id
+            .0
+            .iter()
+            .position(|id| *id as usize == external_asset_id)
+            .ok_or(OracleError::WrongAssetId(external_asset_id))
+    }
+
+    /// Position of the asset that can be committed and calculated.
+    fn get_active_asset_index(&self, external_asset_id: usize) -> Result<usize, OracleError>
+    {
+        let index = self.get_asset_index(external_asset_id)?;
+        if self.assets_deprecated.0[index]
+        {
+            Err(OracleError::DeprecatedAsset(external_asset_id))
+        }
+        else
+        {
+            Ok(index)
         }
     }
 
+    pub fn is_closed(&self) -> bool
+    {
+        self.closed_at.is_some()
+    }
+
+    pub fn get_sources_threshold(&self) -> u8
+    {
+        self.sources_threshold
+    }
+
+    /// Changes the maximum of sources. Thresholds of assets can't be greater than it.
+    pub fn set_sources_threshold(&mut self, sources_threshold: u8) -> Result<(), OracleError>
+    {
+        if sources_threshold == 0
+            || self
+                .assets_threshold
+                .0
+                .iter()
+                .any(|threshold| *threshold > sources_threshold)
+        {
+            return Err(OracleError::WrongThreshold(sources_threshold));
+        }
+
+        self.sources_threshold = sources_threshold;
+        Ok(())
+    }
+
+    pub fn rename_asset(
+        &mut self,
+        external_asset_id: usize,
+        name: RawString,
+    ) -> Result<(), OracleError>
+    {
+        let index = self.get_asset_index(external_asset_id)?;
+        self.assets_name.0[index] = name;
+        Ok(())
+    }
+
+    fn check_threshold(&self, threshold: u8) -> Result<(), OracleError>
+    {
+        if threshold == 0 || threshold > self.sources_threshold
+        {
+            Err(OracleError::WrongThreshold(threshold))
+        }
+        else
+        {
+            Ok(())
+        }
+    }
+
+    /// Sets thresholds of all assets, in the order of `assets_id`.
+    pub fn set_assets_threshold(&mut self, thresholds: AssetsVec<u8>) -> Result<(), OracleError>
+    {
+        if thresholds.0.len() != self.get_assets_count()
+        {
+            return Err(OracleError::WrongAssetId(thresholds.0.len()));
+        }
+        thresholds
+            .0
+            .iter()
+            .try_for_each(|threshold| self.check_threshold(*threshold))?;
+
+        self.assets_threshold = thresholds;
+        Ok(())
+    }
+
+    pub fn get_asset_threshold(&self, external_asset_id: usize) -> u8
+    {
+        self.get_asset_index(external_asset_id)
+            .map(|index| self.assets_threshold.0[index])
+            .unwrap_or(self.sources_threshold)
+    }
+
+    /// Minimum of sources required to calculate at least one active asset.
+    pub fn get_min_threshold(&self) -> u8
+    {
+        self.assets_threshold
+            .0
+            .iter()
+            .zip(self.assets_deprecated.0.iter())
+            .filter(|(_, deprecated)| !**deprecated)
+            .map(|(threshold, _)| *threshold)
+            .min()
+            .unwrap_or(self.sources_threshold)
+    }
+
     pub fn is_calculate_time(&self, external_asset_id: usize, now: Moment<T>) -> bool
     {
-        external_asset_id < self.get_assets_count()
-            && self
-                .period_handler
-                .is_calculate_time(self.value.0[external_asset_id].last_changed, now)
+        self.get_active_asset_index(external_asset_id)
+            .map(|index| {
+                self.period_handler
+                    .is_calculate_time(self.value.0[index].last_changed, now)
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn get_value(&self, external_asset_id: usize) -> Option<&ExternalValue<T>>
+    {
+        self.get_asset_index(external_asset_id)
+            .ok()
+            .map(|index| &self.value.0[index])
     }
 
     pub fn get_assets_count(&self) -> usize
@@ -106,15 +290,99 @@ impl<T: Trait> Oracle<T>
         self.assets_name.0.len()
     }
 
-    pub fn add_asset(&mut self, name: RawString)
+    /// Adds the asset and returns its id.
+    pub fn add_asset(&mut self, name: RawString, threshold: u8) -> Result<u8, OracleError>
     {
+        self.check_threshold(threshold)?;
+        let id = self.next_asset_id.ok_or(OracleError::TooManyAssets)?;
+
+        self.next_asset_id = id.checked_add(1);
+        self.assets_id.0.push(id);
         self.assets_name.0.push(name);
+        self.assets_threshold.0.push(threshold);
+        self.assets_deprecated.0.push(false);
         self.value.0.push(ExternalValue::new());
+        self.excluded_sources.0.push(Vec::new());
+        self.sources
+            .values_mut()
+            .for_each(|assets| assets.0.push(ExternalValue::new()));
+        Ok(id)
+    }
+
+    /// Stops commits and calculations of the asset. Its last value stays available.
+    pub fn deprecate_asset(&mut self, external_asset_id: usize) -> Result<(), OracleError>
+    {
+        let index = self.get_active_asset_index(external_asset_id)?;
+        self.assets_deprecated.0[index] = true;
+        Ok(())
+    }
+
+    /// Moves the assets to the order of `assets_id`, which must have every asset id once.
+    /// Values of the sources are moved with their assets.
+    pub fn reorder_assets(&mut self, assets_id: Vec<u8>) -> Result<(), OracleError>
+    {
+        if assets_id.len() != self.get_assets_count()
+        {
+            return Err(OracleError::WrongAssetId(assets_id.len()));
+        }
+
+        let mut indices = BTreeSet::new();
+        let order = assets_id
+            .iter()
+            .map(|id| {
+                let index = self.get_asset_index(*id as usize)?;
+                if indices.insert(index)
+                {
+                    Ok(index)
+                }
+                else
+                {
+                    Err(OracleError::WrongAssetId(*id as usize))
+                }
+            })
+            .collect::<Result<Vec<usize>, OracleError>>()?;
+
+        self.assets_id = reorder(&self.assets_id, &order);
+        self.assets_name = reorder(&self.assets_name, &order);
+        self.assets_threshold = reorder(&self.assets_threshold, &order);
+        self.assets_deprecated = reorder(&self.assets_deprecated, &order);
+        self.value = reorder(&self.value, &order);
+        self.excluded_sources = reorder(&self.excluded_sources, &order);
+        self.sources
+            .values_mut()
+            .for_each(|assets| *assets = reorder(assets, &order));
+        Ok(())
+    }
+
+    /// Removes the deprecated asset with its values, including the values of the sources.
+    pub fn remove_asset(&mut self, external_asset_id: usize) -> Result<(), OracleError>
+    {
+        let index = self.get_asset_index(external_asset_id)?;
+        if !self.assets_deprecated.0[index]
+        {
+            return Err(OracleError::NotDeprecatedAsset(external_asset_id));
+        }
+
+        self.assets_id.0.remove(index);
+        self.assets_name.0.remove(index);
+        self.assets_threshold.0.remove(index);
+        self.assets_deprecated.0.remove(index);
+        self.value.0.remove(index);
+        self.excluded_sources.0.remove(index);
+        self.sources.values_mut().for_each(|assets| {
+            assets.0.remove(index);
+        });
+        Ok(())
+    }
+
+    pub fn get_source_stake(&self, account: &AccountId<T>) -> Balance<T>
+    {
+        self.sources_stake.get(account).cloned().unwrap_or_default()
     }
 
     pub fn update_accounts<I>(&mut self, accounts: I) -> Result<(), OracleError>
     where
-        I: Iterator<Item = AccountId<T>>,
+        I: Iterator<Item = (AccountId<T>, Balance<T>)>,
     {
         let mut default_external_value: AssetsVec<ExternalValue<T>> = self.value.clone();
         default_external_value
@@ -122,27 +390,54 @@ impl<T: Trait> Oracle<T>
             .iter_mut()
             .for_each(|val| val.clean());
 
+        let accounts: Vec<(AccountId<T>, Balance<T>)> = accounts.collect();
+
         self.sources = accounts
-            .map(|account| {
+            .iter()
+            .map(|(account, _)| {
                 let external_value = self
                     .sources
-                    .get(&account)
+                    .get(account)
                     .unwrap_or(&default_external_value)
                     .clone();
-                (account, external_value)
+                (account.clone(), external_value)
             })
             .collect();
+        self.sources_stake = accounts.into_iter().collect();
 
-        match (self.sources.len() as u8).cmp(&self.sources_threshold)
+        let sources = &self.sources;
+        self.commitments = self
+            .commitments
+            .iter()
+            .filter(|(account, _)| sources.contains_key(account))
+            .map(|(account, commitment)| (account.clone(), commitment.clone()))
+            .collect();
+
+        let threshold = self.get_min_threshold();
+        match (self.sources.len() as u8).cmp(&threshold)
         {
             Ordering::Less => Err(OracleError::TooFewAccounts(
-                self.sources_threshold as usize,
+                threshold as usize,
                 self.sources.len() as usize,
             )),
             _ => Ok(()),
         }
     }
 
+    /// Whether the source has already committed some value at `now`. Values can't be
+    /// committed twice at the same moment.
+    pub fn is_committed_at(&self, account: &AccountId<T>, now: Moment<T>) -> bool
+    {
+        self.sources.get(account).map_or(false, |assets| {
+            assets
+                .0
+                .iter()
+                .any(|external| external.last_changed == Some(now))
+        })
+    }
+
+    /// Updates all active assets of the source, `values` are in the order
+    /// of `assets_id` without deprecated assets.
     pub fn commit_value(
         &mut self,
         account: &AccountId<T>,
@@ -150,13 +445,16 @@ impl<T: Trait> Oracle<T>
         now: Moment<T>,
     ) -> Result<(), OracleError>
     {
+        let deprecated = &self.assets_deprecated;
         if let Some(assets) = self.sources.get_mut(account)
         {
             assets
                 .0
                 .iter_mut()
+                .zip(deprecated.0.iter())
+                .filter(|(_, deprecated)| !**deprecated)
                 .zip(values.0.iter())
-                .for_each(|(external, new_val)| external.update(*new_val, now));
+                .for_each(|((external, _), new_val)| external.update(*new_val, now));
             Ok(())
         }
         else
@@ -165,62 +463,272 @@ impl<T: Trait> Oracle<T>
         }
     }
 
-    pub fn calculate_median(
+    pub fn is_commit_reveal(&self) -> bool
+    {
+        self.period_handler.is_reveal_enabled()
+    }
+
+    /// Stores the hash of `(values, salt)` of the source until the reveal.
+    pub fn commit_hash(
         &mut self,
-        number: usize,
+        account: &AccountId<T>,
+        hash: T::Hash,
         now: Moment<T>,
-    ) -> Result<T::ValueType, OracleError>
+    ) -> Result<(), OracleError>
     {
-        if number >= self.get_assets_count()
+        if !self.sources.contains_key(account)
         {
-            return Err(OracleError::WrongAssetId(number));
+            return Err(OracleError::AccountAccess);
         }
 
-        if self.sources.len() < self.sources_threshold as usize
+        self.commitments.insert(account.clone(), (hash, now));
+        Ok(())
+    }
+
+    /// Commits values of the source if they match the hash committed in the same period.
+    /// The hash is of SCALE encoded `(oracle_id, account, values, salt)`, so a commitment
+    /// can't be copied by another source or to another oracle.
+    pub fn reveal_value(
+        &mut self,
+        oracle_id: T::OracleId,
+        account: &AccountId<T>,
+        values: AssetsVec<T::ValueType>,
+        salt: RawString,
+        now: Moment<T>,
+    ) -> Result<(), OracleError>
+    {
+        let (hash, committed) = self
+            .commitments
+            .get(account)
+            .cloned()
+            .ok_or(OracleError::WrongReveal)?;
+
+        if self.period_handler.get_period(committed) != self.period_handler.get_period(now)
+            || T::Hashing::hash_of(&(oracle_id, account, &values, &salt)) != hash
+        {
+            return Err(OracleError::WrongReveal);
+        }
+
+        self.commitments.remove(account);
+        self.commit_value(account, values, now)
+    }
+
+    /// Updates only the given assets of the source. Fails without changes
+    /// if any asset id is unknown, deprecated or repeated.
+    pub fn commit_sparse_value(
+        &mut self,
+        account: &AccountId<T>,
+        values: Vec<(usize, T::ValueType)>,
+        now: Moment<T>,
+    ) -> Result<(), OracleError>
+    {
+        if !self.sources.contains_key(account)
+        {
+            return Err(OracleError::AccountAccess);
+        }
+
+        let mut numbers = BTreeSet::new();
+        let values = values
+            .into_iter()
+            .map(|(number, value)| {
+                let index = self.get_active_asset_index(number)?;
+                if numbers.insert(number)
+                {
+                    Ok((index, value))
+                }
+                else
+                {
+                    Err(OracleError::WrongAssetId(number))
+                }
+            })
+            .collect::<Result<Vec<(usize, T::ValueType)>, OracleError>>()?;
+
+        if let Some(assets) = self.sources.get_mut(account)
+        {
+            values
+                .into_iter()
+                .for_each(|(index, value)| assets.0[index].update(value, now));
+        }
+        Ok(())
+    }
+
+    pub fn calculate_median(
+        &mut self,
+        external_asset_id: usize,
+        now: Moment<T>,
+    ) -> Result<T::ValueType, OracleError>
+    {
+        let number = self.get_active_asset_index(external_asset_id)?;
+
+        let threshold = self.assets_threshold.0[number];
+        if self.sources.len() < threshold as usize
         {
             return Err(OracleError::TooFewAccounts(
-                self.sources_threshold as usize,
+                threshold as usize,
                 self.sources.len() as usize,
             ));
         }
 
-        let assets: Vec<&T::ValueType> = self
+        let assets: Vec<(AccountId<T>, T::ValueType)> = self
             .sources
             .iter()
-            .map(|(_, assets)| assets.0.get(number))
-            .filter(|external| external.and_then(|ext| ext.value).is_some())
-            .map(|external| {
-                external
-                    .as_ref()
-                    .map(|ext| ext.value.as_ref().unwrap())
-                    .unwrap()
+            .filter_map(|(account, assets)| {
+                assets
+                    .0
+                    .get(number)
+                    .filter(|external| self.is_actual(external, now))
+                    .and_then(|external| external.value)
+                    .map(|value| (account.clone(), value))
             })
             .collect();
+        Self::check_commited_count(threshold, assets.len())?;
+
+        let (assets, excluded) = self.outlier_filter.filter(assets);
+        Self::check_commited_count(threshold, assets.len())?;
+
+        let values: Vec<T::ValueType> = assets.iter().map(|(_, value)| *value).collect();
+        let metadata = ValueMetadata::from_values(&values).ok_or(OracleError::CalculationError)?;
+
+        let assets: Vec<(T::ValueType, Balance<T>)> = assets
+            .into_iter()
+            .map(|(account, value)| (value, self.get_source_stake(&account)))
+            .collect();
+
+        self.aggregator
+            .aggregate(assets)
+            .map_or(Err(OracleError::CalculationError), |med| {
+                self.value.0[number].update_with_metadata(med, metadata, now);
+                self.excluded_sources.0[number] = excluded;
+                Ok(med)
+            })
+    }
 
-        if assets.len() < self.sources_threshold as usize
+    /// Sources that committed the asset in the current aggregation window values that
+    /// deviate from `calculated` more than the slash rule allows, so stale values are
+    /// never slashed again. Empty if the oracle has no slash rule.
+    pub fn get_deviating_sources(
+        &self,
+        external_asset_id: usize,
+        calculated: T::ValueType,
+        now: Moment<T>,
+    ) -> Vec<AccountId<T>>
+    {
+        match self.slash_rule
         {
-            return Err(OracleError::TooFewCommitedValue(
-                self.sources_threshold as usize,
-                assets.len() as usize,
-            ));
+            Some(rule) => self.filter_sources(external_asset_id, |external| {
+                match (external.value, external.last_changed)
+                {
+                    (Some(value), Some(changed)) =>
+                    {
+                        self.period_handler.get_window_age(changed, now).is_zero()
+                            && rule.is_deviating(value, calculated)
+                    }
+                    _ => false,
+                }
+            }),
+            None => Vec::new(),
         }
+    }
 
-        match get_median(assets)
+    /// Sources that committed the asset in the current aggregation window values close
+    /// to `calculated`, as the reward rule requires. Empty if the oracle has no reward rule.
+    pub fn get_rewarded_sources(
+        &self,
+        external_asset_id: usize,
+        calculated: T::ValueType,
+        now: Moment<T>,
+    ) -> Vec<AccountId<T>>
+    {
+        match self.reward_rule
         {
-            Some(Median::Value(value)) => Some(value.clone()),
-            Some(Median::Pair(left, right)) =>
-            {
-                let sum = *left + *right;
-                let divider: T::ValueType = One::one();
+            Some(rule) => self.filter_sources(external_asset_id, |external| {
+                match (external.value, external.last_changed)
+                {
+                    (Some(value), Some(changed)) =>
+                    {
+                        self.period_handler.get_window_age(changed, now).is_zero()
+                            && rule.is_rewarded(value, calculated)
+                    }
+                    _ => false,
+                }
+            }),
+            None => Vec::new(),
+        }
+    }
 
-                Some(sum / (divider + One::one()))
-            }
-            _ => None,
+    fn filter_sources<F>(&self, external_asset_id: usize, predicate: F) -> Vec<AccountId<T>>
+    where
+        F: Fn(&ExternalValue<T>) -> bool,
+    {
+        match self.get_asset_index(external_asset_id)
+        {
+            Ok(number) => self
+                .sources
+                .iter()
+                .filter(|(_, assets)| {
+                    assets
+                        .0
+                        .get(number)
+                        .map_or(false, |external| predicate(external))
+                })
+                .map(|(account, _)| account.clone())
+                .collect(),
+            Err(_) => Vec::new(),
         }
-        .map_or(Err(OracleError::CalculationError), |med| {
-            self.value.0[number].update(med, now);
-            Ok(med)
-        })
+    }
+
+    /// Number of assets that are committed and calculated.
+    pub fn get_active_assets_count(&self) -> usize
+    {
+        self.assets_deprecated
+            .0
+            .iter()
+            .filter(|deprecated| !**deprecated)
+            .count()
+    }
+
+    /// Ids of the assets that are committed and calculated, in the order of `assets_id`.
+    pub fn get_active_assets_id(&self) -> Vec<u8>
+    {
+        self.assets_id
+            .0
+            .iter()
+            .zip(self.assets_deprecated.0.iter())
+            .filter(|(_, deprecated)| !**deprecated)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Checks the source value against the staleness policy.
+    fn is_actual(&self, external: &ExternalValue<T>, now: Moment<T>) -> bool
+    {
+        match external.last_changed
+        {
+            Some(changed) => self
+                .staleness
+                .is_actual(self.period_handler.get_window_age(changed, now)),
+            None => false,
+        }
+    }
+
+    fn check_commited_count(threshold: u8, count: usize) -> Result<(), OracleError>
+    {
+        if count < threshold as usize
+        {
+            Err(OracleError::TooFewCommitedValue(threshold as usize, count))
+        }
+        else
+        {
+            Ok(())
+        }
+    }
+}
+
+/// Values of `values` at the positions of `order`.
+fn reorder<V: Clone>(values: &AssetsVec<V>, order: &[usize]) -> AssetsVec<V>
+{
+    AssetsVec {
+        0: order.iter().map(|index| values.0[*index].clone()).collect(),
     }
 }
 
@@ -232,7 +740,7 @@ mod tests
     type Oracle = super::Oracle<Test>;
     use super::OracleError;
     type Moment = crate::module_trait::Moment<Test>;
-    use super::{AssetsVec, PeriodHandler};
+    use super::{AggregatorType, AssetsVec, OutlierFilter, PeriodHandler, StalenessPolicy};
 
     fn get_period_handler() -> PeriodHandler<Moment>
     {
@@ -247,12 +755,35 @@ mod tests
     }
 
     fn get_oracle() -> Oracle
+    {
+        get_oracle_with_aggregator(AggregatorType::Median)
+    }
+
+    fn get_oracle_with_aggregator(aggregator: AggregatorType) -> Oracle
+    {
+        get_oracle_with(aggregator, OutlierFilter::Disabled)
+    }
+
+    fn get_oracle_with(aggregator: AggregatorType, outlier_filter: OutlierFilter) -> Oracle
+    {
+        get_oracle_with_staleness(aggregator, outlier_filter, StalenessPolicy::Any)
+    }
+
+    fn get_oracle_with_staleness(
+        aggregator: AggregatorType,
+        outlier_filter: OutlierFilter,
+        staleness: StalenessPolicy,
+    ) -> Oracle
     {
         Oracle::new(
             "test".to_owned().as_bytes().to_vec(),
             0,
+            0,
             get_period_handler(),
             9,
+            aggregator,
+            outlier_filter,
+            staleness,
             get_assets_vec(
                 vec!["f", "s", "t"]
                     .iter()
@@ -261,6 +792,13 @@ mod tests
         )
     }
 
+    fn with_stake<I>(accounts: I) -> impl Iterator<Item = (u64, u128)>
+    where
+        I: Iterator<Item = u64>,
+    {
+        accounts.map(|account| (account, 1))
+    }
+
     fn update_values(oracle: &mut Oracle, accounts: Vec<u64>, now: Moment, values: Vec<Vec<u128>>)
     {
         for account in accounts.into_iter()
@@ -309,7 +847,7 @@ mod tests
         let mut oracle = get_oracle();
         assert_eq!(oracle.value.0.len(), 3);
         assert_eq!(
-            oracle.update_accounts(1..=3),
+            oracle.update_accounts(with_stake(1..=3)),
             Err(OracleError::TooFewAccounts(9, 3))
         );
         update_oracle(
@@ -329,7 +867,7 @@ mod tests
     {
         let mut oracle = get_oracle();
         assert_eq!(oracle.value.0.len(), 3);
-        assert_eq!(oracle.update_accounts(0..=10), Ok(()));
+        assert_eq!(oracle.update_accounts(with_stake(0..=10)), Ok(()));
 
         update_values(
             &mut oracle,
@@ -348,7 +886,7 @@ mod tests
     fn simple_calculate_median()
     {
         let mut oracle = get_oracle();
-        assert_eq!(oracle.update_accounts(0..=10), Ok(()));
+        assert_eq!(oracle.update_accounts(with_stake(0..=10)), Ok(()));
         update_oracle(
             &mut oracle,
             101,
@@ -358,13 +896,23 @@ mod tests
         assert_eq!(oracle.calculate_median(0, 102), Ok(1));
         assert_eq!(oracle.calculate_median(1, 102), Ok(2));
         assert_eq!(oracle.calculate_median(2, 102), Ok(3));
+
+        let metadata = oracle
+            .get_value(1)
+            .and_then(|value| value.metadata.clone())
+            .unwrap();
+        assert_eq!(metadata.sources_count, 11);
+        assert_eq!(
+            (metadata.min, metadata.max, metadata.mad, metadata.std_dev),
+            (2, 2, 0, 0)
+        );
     }
 
     #[test]
     fn calculate_median()
     {
         let mut oracle = get_oracle();
-        assert_eq!(oracle.update_accounts(0..=11), Ok(()));
+        assert_eq!(oracle.update_accounts(with_stake(0..=11)), Ok(()));
         update_oracle(
             &mut oracle,
             101,
@@ -374,6 +922,482 @@ mod tests
                 vec![0; 12],
             ],
         );
-        assert_eq!(oracle.calculate_median(0, 102), Ok(106));
+        assert_eq!(oracle.calculate_median(0, 102), Ok(105));
+    }
+
+    #[test]
+    fn calculate_stake_weighted_median()
+    {
+        let mut oracle = get_oracle_with_aggregator(AggregatorType::StakeWeightedMedian);
+        assert_eq!(
+            oracle.update_accounts(
+                (0..=9).map(|account| (account, if account == 9 { 100 } else { 1 }))
+            ),
+            Ok(())
+        );
+        update_oracle(
+            &mut oracle,
+            101,
+            vec![
+                (100..=109u128).collect::<Vec<u128>>(),
+                vec![0; 10],
+                vec![0; 10],
+            ],
+        );
+        assert_eq!(oracle.calculate_median(0, 102), Ok(109));
+
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            101,
+            vec![
+                (100..=109u128).collect::<Vec<u128>>(),
+                vec![0; 10],
+                vec![0; 10],
+            ],
+        );
+        assert_eq!(oracle.calculate_median(0, 102), Ok(104));
+    }
+
+    #[test]
+    fn calculate_mean()
+    {
+        let mut oracle = get_oracle_with_aggregator(AggregatorType::Mean);
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            101,
+            vec![
+                (100..=109u128).collect::<Vec<u128>>(),
+                vec![0; 10],
+                vec![0; 10],
+            ],
+        );
+        assert_eq!(oracle.calculate_median(0, 102), Ok(104));
+    }
+
+    #[test]
+    fn calculate_with_outlier_filter()
+    {
+        let mut oracle = get_oracle_with(AggregatorType::Mean, OutlierFilter::Mad(300));
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        let mut values: Vec<u128> = (100..=108).collect();
+        values.push(1000);
+        update_oracle(&mut oracle, 101, vec![values, vec![0; 10], vec![0; 10]]);
+
+        assert_eq!(oracle.calculate_median(0, 102), Ok(104));
+        assert_eq!(oracle.excluded_sources.0[0], vec![9]);
+
+        let metadata = oracle
+            .get_value(0)
+            .and_then(|value| value.metadata.clone())
+            .unwrap();
+        assert_eq!(metadata.sources_count, 9);
+        assert_eq!((metadata.min, metadata.max), (100, 108));
+        assert_eq!(metadata.mad, 2);
+        assert_eq!(metadata.std_dev, 2);
+        assert!(oracle.excluded_sources.0[1].is_empty());
+    }
+
+    #[test]
+    fn calculate_error_few_values_after_outlier_filter()
+    {
+        let mut oracle = get_oracle_with(AggregatorType::Median, OutlierFilter::Mad(300));
+        assert_eq!(oracle.update_accounts(with_stake(0..=8)), Ok(()));
+        let mut values: Vec<u128> = (100..=107).collect();
+        values.push(1000);
+        update_oracle(&mut oracle, 101, vec![values, vec![0; 9], vec![0; 9]]);
+
+        assert_eq!(
+            oracle.calculate_median(0, 102),
+            Err(OracleError::TooFewCommitedValue(9, 8))
+        );
+        assert_eq!(oracle.value.0[0].value, None);
+    }
+
+    #[test]
+    fn assets_threshold()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(
+            oracle.set_assets_threshold(get_assets_vec(vec![9, 10, 9].into_iter())),
+            Err(OracleError::WrongThreshold(10))
+        );
+        assert_eq!(
+            oracle.set_assets_threshold(get_assets_vec(vec![9, 3].into_iter())),
+            Err(OracleError::WrongAssetId(2))
+        );
+        assert_eq!(
+            oracle.set_assets_threshold(get_assets_vec(vec![9, 3, 9].into_iter())),
+            Ok(())
+        );
+        assert_eq!(
+            oracle.add_asset("fourth".to_owned().as_bytes().to_vec(), 0),
+            Err(OracleError::WrongThreshold(0))
+        );
+        assert_eq!(
+            oracle.add_asset("fourth".to_owned().as_bytes().to_vec(), 4),
+            Ok(3)
+        );
+        assert_eq!(oracle.get_min_threshold(), 3);
+
+        assert_eq!(
+            oracle.update_accounts(with_stake(0..=1)),
+            Err(OracleError::TooFewAccounts(3, 2))
+        );
+        assert_eq!(oracle.update_accounts(with_stake(0..=4)), Ok(()));
+        assert!(oracle.sources.values().all(|assets| assets.0.len() == 4));
+
+        update_values(
+            &mut oracle,
+            (0..=2).collect(),
+            101,
+            vec![vec![1; 5], vec![2; 5], vec![3; 5], vec![4; 5]],
+        );
+
+        assert_eq!(
+            oracle.calculate_median(0, 102),
+            Err(OracleError::TooFewAccounts(9, 5))
+        );
+        assert_eq!(oracle.calculate_median(1, 102), Ok(2));
+        assert_eq!(
+            oracle.calculate_median(3, 102),
+            Err(OracleError::TooFewCommitedValue(4, 3))
+        );
+
+        assert_eq!(oracle.deprecate_asset(1), Ok(()));
+        assert_eq!(oracle.get_min_threshold(), 4);
+        assert_eq!(
+            oracle.update_accounts(with_stake(0..=2)),
+            Err(OracleError::TooFewAccounts(4, 3))
+        );
+    }
+
+    #[test]
+    fn commit_sparse()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            101,
+            vec![vec![1; 10], vec![2; 10], vec![3; 10]],
+        );
+
+        assert_eq!(
+            oracle.commit_sparse_value(&10, vec![(0, 5)], 102),
+            Err(OracleError::AccountAccess)
+        );
+        assert_eq!(
+            oracle.commit_sparse_value(&0, vec![(0, 5), (3, 5)], 102),
+            Err(OracleError::WrongAssetId(3))
+        );
+        assert_eq!(
+            oracle.commit_sparse_value(&0, vec![(2, 5), (0, 5), (2, 6)], 102),
+            Err(OracleError::WrongAssetId(2))
+        );
+        assert_eq!(oracle.sources[&0].0[0].value, Some(1));
+
+        assert_eq!(oracle.commit_sparse_value(&0, vec![(2, 30)], 102), Ok(()));
+        assert_eq!(oracle.sources[&0].0[0].value, Some(1));
+        assert_eq!(oracle.sources[&0].0[0].last_changed, Some(101));
+        assert_eq!(oracle.sources[&0].0[1].value, Some(2));
+        assert_eq!(oracle.sources[&0].0[2].value, Some(30));
+        assert_eq!(oracle.sources[&0].0[2].last_changed, Some(102));
+    }
+
+    #[test]
+    fn calculate_without_stale_values()
+    {
+        let mut oracle = get_oracle_with_staleness(
+            AggregatorType::Median,
+            OutlierFilter::Disabled,
+            StalenessPolicy::MaxAge(1),
+        );
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+
+        update_values(&mut oracle, (0..=9).collect(), 105, vec![vec![1; 10]; 3]);
+        update_values(&mut oracle, (0..=5).collect(), 115, vec![vec![2; 10]; 3]);
+        assert_eq!(oracle.calculate_median(0, 120), Ok(2));
+
+        update_values(&mut oracle, (4..=9).collect(), 125, vec![vec![3; 10]; 3]);
+        assert_eq!(
+            oracle.calculate_median(0, 136),
+            Err(OracleError::TooFewCommitedValue(9, 6))
+        );
+
+        oracle.staleness = StalenessPolicy::CurrentWindow;
+        assert_eq!(
+            oracle.calculate_median(0, 130),
+            Err(OracleError::TooFewCommitedValue(9, 6))
+        );
+        oracle.staleness = StalenessPolicy::Any;
+        assert_eq!(oracle.calculate_median(0, 130), Ok(3));
+    }
+
+    #[test]
+    fn set_sources_threshold()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(
+            oracle.set_assets_threshold(get_assets_vec(vec![5, 7, 3].into_iter())),
+            Ok(())
+        );
+
+        assert_eq!(
+            oracle.set_sources_threshold(0),
+            Err(OracleError::WrongThreshold(0))
+        );
+        assert_eq!(
+            oracle.set_sources_threshold(6),
+            Err(OracleError::WrongThreshold(6))
+        );
+        assert_eq!(oracle.get_sources_threshold(), 9);
+
+        assert_eq!(oracle.set_sources_threshold(7), Ok(()));
+        assert_eq!(oracle.get_sources_threshold(), 7);
+        assert_eq!(
+            oracle.add_asset(b"fo".to_vec(), 8),
+            Err(OracleError::WrongThreshold(8))
+        );
+    }
+
+    #[test]
+    fn rename_asset()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.rename_asset(1, b"second".to_vec()), Ok(()));
+        assert_eq!(oracle.assets_name.0[1], b"second".to_vec());
+        assert_eq!(
+            oracle.rename_asset(3, b"fourth".to_vec()),
+            Err(OracleError::WrongAssetId(3))
+        );
+    }
+
+    #[test]
+    fn asset_lifecycle()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            105,
+            vec![vec![1; 10], vec![2; 10], vec![3; 10]],
+        );
+
+        assert_eq!(
+            oracle.remove_asset(1),
+            Err(OracleError::NotDeprecatedAsset(1))
+        );
+        assert_eq!(oracle.deprecate_asset(1), Ok(()));
+        assert_eq!(
+            oracle.deprecate_asset(1),
+            Err(OracleError::DeprecatedAsset(1))
+        );
+        assert!(!oracle.is_calculate_time(1, 110));
+        assert_eq!(
+            oracle.calculate_median(1, 110),
+            Err(OracleError::DeprecatedAsset(1))
+        );
+        assert_eq!(
+            oracle.commit_sparse_value(&0, vec![(1, 5)], 106),
+            Err(OracleError::DeprecatedAsset(1))
+        );
+
+        assert_eq!(oracle.remove_asset(1), Ok(()));
+        assert_eq!(oracle.assets_id.0, vec![0, 2]);
+        assert_eq!(oracle.get_value(1), None);
+        assert!(oracle
+            .sources
+            .values()
+            .all(|assets| assets.0.len() == 2 && assets.0[1].value == Some(3)));
+        assert_eq!(oracle.calculate_median(2, 110), Ok(3));
+
+        assert_eq!(oracle.add_asset(b"fourth".to_vec(), 9), Ok(3));
+        assert_eq!(oracle.assets_id.0, vec![0, 2, 3]);
+        assert_eq!(oracle.commit_sparse_value(&0, vec![(3, 4)], 106), Ok(()));
+        assert_eq!(oracle.sources[&0].0[2].value, Some(4));
+        assert_eq!(
+            oracle.commit_sparse_value(&0, vec![(1, 4)], 106),
+            Err(OracleError::WrongAssetId(1))
+        );
+    }
+
+    #[test]
+    fn reorder_assets()
+    {
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            101,
+            vec![vec![1; 10], vec![2; 10], vec![3; 10]],
+        );
+
+        assert_eq!(
+            oracle.reorder_assets(vec![2, 0]),
+            Err(OracleError::WrongAssetId(2))
+        );
+        assert_eq!(
+            oracle.reorder_assets(vec![2, 0, 0]),
+            Err(OracleError::WrongAssetId(0))
+        );
+        assert_eq!(
+            oracle.reorder_assets(vec![2, 0, 3]),
+            Err(OracleError::WrongAssetId(3))
+        );
+        assert_eq!(oracle.assets_id.0, vec![0, 1, 2]);
+
+        assert_eq!(oracle.reorder_assets(vec![2, 0, 1]), Ok(()));
+        assert_eq!(oracle.assets_id.0, vec![2, 0, 1]);
+        assert_eq!(oracle.assets_name.0[0], b"t".to_vec());
+        assert_eq!(oracle.sources[&0].0[0].value, Some(3));
+        assert_eq!(oracle.calculate_median(2, 102), Ok(3));
+        assert_eq!(oracle.value.0[0].value, Some(3));
+
+        // Full commits skip deprecated assets
+        assert_eq!(oracle.deprecate_asset(0), Ok(()));
+        assert_eq!(oracle.get_active_assets_id(), vec![2, 1]);
+        assert_eq!(
+            oracle.commit_value(&0, get_assets_vec(vec![30, 20].into_iter()), 103),
+            Ok(())
+        );
+        assert_eq!(oracle.sources[&0].0[0].value, Some(30));
+        assert_eq!(oracle.sources[&0].0[1].value, Some(1));
+        assert_eq!(oracle.sources[&0].0[2].value, Some(20));
+        assert!(oracle.is_committed_at(&0, 103));
+        assert!(!oracle.is_committed_at(&1, 103));
+        assert!(!oracle.is_committed_at(&0, 104));
+    }
+
+    #[test]
+    fn commit_reveal()
+    {
+        use sr_primitives::traits::{BlakeTwo256, Hash};
+
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.period_handler.set_reveal_period(2), Ok(()));
+        assert!(oracle.is_commit_reveal());
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+
+        let values = get_assets_vec(vec![1u128, 2, 3].into_iter());
+        let salt = b"salt".to_vec();
+        let hash = |oracle_id: u64, account: u64| {
+            BlakeTwo256::hash_of(&(oracle_id, account, &values, &salt))
+        };
+
+        assert_eq!(
+            oracle.commit_hash(&10, hash(0, 10), 105),
+            Err(OracleError::AccountAccess)
+        );
+        assert_eq!(
+            oracle.reveal_value(0, &0, values.clone(), salt.clone(), 108),
+            Err(OracleError::WrongReveal)
+        );
+
+        assert_eq!(oracle.commit_hash(&0, hash(0, 0), 105), Ok(()));
+        // Source 1 copies the commitment of source 0
+        assert_eq!(oracle.commit_hash(&1, hash(0, 0), 105), Ok(()));
+        assert_eq!(
+            oracle.reveal_value(0, &0, values.clone(), b"other".to_vec(), 108),
+            Err(OracleError::WrongReveal)
+        );
+        assert_eq!(
+            oracle.reveal_value(1, &0, values.clone(), salt.clone(), 108),
+            Err(OracleError::WrongReveal)
+        );
+        assert_eq!(oracle.sources[&0].0[0].value, None);
+
+        assert_eq!(
+            oracle.reveal_value(0, &0, values.clone(), salt.clone(), 108),
+            Ok(())
+        );
+        assert_eq!(oracle.sources[&0].0[2].value, Some(3));
+        assert!(!oracle.commitments.contains_key(&0));
+
+        assert_eq!(
+            oracle.reveal_value(0, &1, values.clone(), salt.clone(), 108),
+            Err(OracleError::WrongReveal)
+        );
+        assert_eq!(oracle.sources[&1].0[2].value, None);
+
+        assert_eq!(oracle.commit_hash(&1, hash(0, 1), 105), Ok(()));
+        assert_eq!(
+            oracle.reveal_value(0, &1, values.clone(), salt.clone(), 118),
+            Err(OracleError::WrongReveal)
+        );
+
+        assert_eq!(oracle.update_accounts(with_stake(2..=11)), Ok(()));
+        assert!(oracle.commitments.is_empty());
+    }
+
+    #[test]
+    fn deviating_sources()
+    {
+        use sr_primitives::Permill;
+
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_oracle(
+            &mut oracle,
+            105,
+            vec![
+                vec![100, 100, 100, 100, 100, 100, 100, 109, 111, 80],
+                vec![1; 10],
+                vec![1; 10],
+            ],
+        );
+        assert_eq!(oracle.calculate_median(0, 110), Ok(100));
+        assert!(oracle.get_deviating_sources(0, 100, 110).is_empty());
+
+        oracle.slash_rule = Some(super::SlashRule {
+            max_deviation: Permill::from_percent(10),
+            slash: Permill::from_percent(50),
+        });
+        assert_eq!(oracle.get_deviating_sources(0, 100, 110), vec![8, 9]);
+        assert!(oracle.get_deviating_sources(1, 1, 110).is_empty());
+        assert!(oracle.get_deviating_sources(3, 100, 110).is_empty());
+
+        // Values of the previous windows are not slashed again
+        assert_eq!(oracle.get_deviating_sources(0, 100, 114), vec![8, 9]);
+        assert!(oracle.get_deviating_sources(0, 100, 120).is_empty());
+    }
+
+    #[test]
+    fn rewarded_sources()
+    {
+        use sr_primitives::Permill;
+
+        let mut oracle = get_oracle();
+        assert_eq!(oracle.update_accounts(with_stake(0..=9)), Ok(()));
+        update_values(&mut oracle, (0..=1).collect(), 105, vec![vec![100; 10]; 3]);
+        update_values(
+            &mut oracle,
+            (2..=9).collect(),
+            115,
+            vec![
+                vec![100, 100, 100, 100, 100, 100, 100, 109, 111, 80],
+                vec![1; 10],
+                vec![1; 10],
+            ],
+        );
+        assert!(oracle.get_rewarded_sources(0, 100, 120).is_empty());
+
+        oracle.reward_rule = Some(super::RewardRule {
+            asset_id: 0,
+            per_period: 100,
+            max_deviation: Permill::from_percent(10),
+        });
+        assert_eq!(
+            oracle.get_rewarded_sources(0, 100, 120),
+            vec![2, 3, 4, 5, 6, 7]
+        );
+        assert!(oracle.get_rewarded_sources(0, 100, 125).is_empty());
+        assert!(oracle.get_rewarded_sources(3, 100, 120).is_empty());
+
+        assert_eq!(oracle.get_active_assets_count(), 3);
+        assert_eq!(oracle.deprecate_asset(2), Ok(()));
+        assert_eq!(oracle.get_active_assets_count(), 2);
     }
 }
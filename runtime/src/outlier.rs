@@ -0,0 +1,188 @@
+use rstd::prelude::*;
+
+use codec::{Decode, Encode};
+use sr_primitives::traits::SimpleArithmetic;
+
+use crate::median::{get_median, Median};
+
+/// Filter applied to the committed values before aggregation.
+/// Multipliers are given in hundredths: `150` means `1.5`.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum OutlierFilter
+{
+    Disabled,
+    /// Excludes values further than `multiplier` median absolute deviations from the median.
+    /// Nothing is excluded while the deviation is zero, i.e. more than half of values are equal.
+    Mad(u32),
+    /// Excludes values outside of `[Q1 - multiplier * IQR, Q3 + multiplier * IQR]`.
+    /// Nothing is excluded while the quartiles are equal.
+    Iqr(u32),
+}
+
+impl Default for OutlierFilter
+{
+    fn default() -> OutlierFilter
+    {
+        OutlierFilter::Disabled
+    }
+}
+
+fn get_median_value<V: SimpleArithmetic + Copy>(values: Vec<V>) -> Option<V>
+{
+    get_median(values).map(Median::value)
+}
+
+fn scale<V: SimpleArithmetic + Copy>(value: V, multiplier: u32) -> V
+{
+    value
+        .checked_mul(&V::from(multiplier))
+        .unwrap_or_else(V::max_value)
+        / V::from(100u32)
+}
+
+/// Median absolute deviation of `values`.
+pub fn get_mad<V: SimpleArithmetic + Copy>(values: &[V]) -> Option<(V, V)>
+{
+    let median = get_median_value(values.to_vec())?;
+    let mad = get_median_value(
+        values
+            .iter()
+            .map(|value| {
+                if *value > median
+                {
+                    *value - median
+                }
+                else
+                {
+                    median - *value
+                }
+            })
+            .collect(),
+    )?;
+
+    Some((median, mad))
+}
+
+/// First and third quartiles of `values`, as medians of the lower and the upper halves.
+pub fn get_quartiles<V: SimpleArithmetic + Copy>(values: &[V]) -> Option<(V, V)>
+{
+    if values.len() < 2
+    {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort();
+
+    let len = sorted.len();
+    Some((
+        get_median_value(sorted[..len / 2].to_vec())?,
+        get_median_value(sorted[(len + 1) / 2..].to_vec())?,
+    ))
+}
+
+impl OutlierFilter
+{
+    /// Band `(low, high)` of accepted values, `None` if all values are accepted.
+    /// A zero spread would shrink the band to one value and exclude every value
+    /// that differs from it even slightly, so such values are not filtered.
+    pub fn get_band<V: SimpleArithmetic + Copy>(&self, values: &[V]) -> Option<(V, V)>
+    {
+        match self
+        {
+            OutlierFilter::Disabled => None,
+            OutlierFilter::Mad(multiplier) => get_mad(values)
+                .filter(|(_, mad)| !mad.is_zero())
+                .map(|(median, mad)| {
+                    let width = scale(mad, *multiplier);
+                    (median.saturating_sub(width), median.saturating_add(width))
+                }),
+            OutlierFilter::Iqr(multiplier) => get_quartiles(values)
+                .filter(|(first, third)| first < third)
+                .map(|(first, third)| {
+                    let width = scale(third - first, *multiplier);
+                    (first.saturating_sub(width), third.saturating_add(width))
+                }),
+        }
+    }
+
+    /// Splits `values` into accepted values and keys of excluded values.
+    pub fn filter<K, V>(&self, values: Vec<(K, V)>) -> (Vec<(K, V)>, Vec<K>)
+    where
+        V: SimpleArithmetic + Copy,
+    {
+        let band = self.get_band(&values.iter().map(|(_, value)| *value).collect::<Vec<V>>());
+
+        match band
+        {
+            Some((low, high)) =>
+            {
+                let (accepted, excluded): (Vec<(K, V)>, Vec<(K, V)>) = values
+                    .into_iter()
+                    .partition(|(_, value)| low <= *value && *value <= high);
+                (accepted, excluded.into_iter().map(|(key, _)| key).collect())
+            }
+            None => (values, Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{get_mad, get_quartiles, OutlierFilter};
+
+    fn with_keys(values: Vec<u128>) -> Vec<(usize, u128)>
+    {
+        values.into_iter().enumerate().collect()
+    }
+
+    #[test]
+    fn disabled()
+    {
+        let (accepted, excluded) = OutlierFilter::Disabled.filter(with_keys(vec![1, 1000]));
+        assert_eq!(accepted.len(), 2);
+        assert!(excluded.is_empty());
+    }
+
+    #[test]
+    fn mad()
+    {
+        assert_eq!(get_mad(&[1u128, 1, 2, 2, 4, 6, 9]), Some((2, 1)));
+
+        let values = vec![100, 101, 102, 103, 104, 105, 106, 107, 108, 1000];
+        let (accepted, excluded) = OutlierFilter::Mad(300).filter(with_keys(values));
+        assert_eq!(accepted.len(), 9);
+        assert_eq!(excluded, vec![9]);
+    }
+
+    #[test]
+    fn zero_spread()
+    {
+        let values = vec![100, 100, 100, 100, 100, 100, 101, 99, 1000];
+        assert_eq!(get_mad(&values), Some((100, 0)));
+        let (accepted, excluded) = OutlierFilter::Mad(300).filter(with_keys(values));
+        assert_eq!(accepted.len(), 9);
+        assert!(excluded.is_empty());
+
+        let values = vec![5, 5, 5, 5, 5, 5, 6, 4];
+        assert_eq!(get_quartiles(&values), Some((5, 5)));
+        let (accepted, excluded) = OutlierFilter::Iqr(150).filter(with_keys(values));
+        assert_eq!(accepted.len(), 8);
+        assert!(excluded.is_empty());
+    }
+
+    #[test]
+    fn iqr()
+    {
+        assert_eq!(get_quartiles(&[1u128, 2, 3, 4, 5, 6, 7, 8]), Some((2, 6)));
+        assert_eq!(get_quartiles(&[1u128, 2, 3, 4, 5, 6, 7]), Some((2, 6)));
+        assert_eq!(get_quartiles(&[1u128]), None);
+
+        let values = vec![0, 10, 11, 12, 13, 14, 15, 16, 17, 40];
+        let (accepted, excluded) = OutlierFilter::Iqr(150).filter(with_keys(values));
+        assert_eq!(accepted.len(), 8);
+        assert_eq!(excluded, vec![0, 9]);
+    }
+}
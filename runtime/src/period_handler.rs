@@ -1,6 +1,41 @@
 use codec::{Decode, Encode};
 use rstd::ops::{Add, Div, Mul, Sub};
-use sr_primitives::traits::One;
+use sr_primitives::traits::{One, Zero};
+
+/// Which committed source values are actual for calculation.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum StalenessPolicy
+{
+    /// Any committed value is used.
+    Any,
+    /// Only values committed in the current aggregation window.
+    CurrentWindow,
+    /// Values committed no more than `n` aggregation windows before the current one.
+    MaxAge(u32),
+}
+
+impl Default for StalenessPolicy
+{
+    fn default() -> StalenessPolicy
+    {
+        StalenessPolicy::Any
+    }
+}
+
+impl StalenessPolicy
+{
+    /// `age` is the number of aggregation windows passed since the value was committed.
+    pub fn is_actual<Time: From<u32> + PartialOrd>(&self, age: Time) -> bool
+    {
+        match self
+        {
+            StalenessPolicy::Any => true,
+            StalenessPolicy::CurrentWindow => age <= Time::from(0),
+            StalenessPolicy::MaxAge(max_age) => age <= Time::from(*max_age),
+        }
+    }
+}
 
 #[derive(Encode, Decode, Clone, Eq, Default, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -10,6 +45,12 @@ pub struct PeriodHandler<Time>
     calculate_period: Time,
     aggregate_period: Time,
     last_sources_update: Time,
+    /// Number of the period that begins at `start`. Changing periods moves `start`
+    /// and continues the numbering, so period numbers never decrease.
+    first_period: Time,
+    /// End of the aggregation window, when the committed hashes are revealed.
+    /// Zero if the commit-reveal mode is disabled.
+    reveal_period: Time,
 }
 
 impl<Time: Default + PartialOrd<Time>> PeriodHandler<Time>
@@ -31,6 +72,8 @@ impl<Time: Default + PartialOrd<Time>> PeriodHandler<Time>
                 aggregate_period,
                 start: now,
                 last_sources_update: Time::default(),
+                first_period: Time::default(),
+                reveal_period: Time::default(),
             })
         }
     }
@@ -38,6 +81,7 @@ impl<Time: Default + PartialOrd<Time>> PeriodHandler<Time>
 
 impl<
         Time: One
+            + Zero
             + Add<Time, Output = Time>
             + Sub<Time, Output = Time>
             + Mul<Time, Output = Time>
@@ -47,21 +91,114 @@ impl<
             + Copy,
     > PeriodHandler<Time>
 {
+    /// Moments before `start` belong to the period preceding `first_period`.
     pub fn get_period(&self, now: Time) -> Time
     {
-        (now - self.start) / self.calculate_period
+        if now >= self.start
+        {
+            self.first_period + (now - self.start) / self.calculate_period
+        }
+        else if self.first_period > Zero::zero()
+        {
+            self.first_period - One::one()
+        }
+        else
+        {
+            Zero::zero()
+        }
+    }
+
+    /// Periods before `first_period` are considered to begin at `start`.
+    pub fn get_period_begin(&self, period: Time) -> Time
+    {
+        if period > self.first_period
+        {
+            self.start + (period - self.first_period) * self.calculate_period
+        }
+        else
+        {
+            self.start
+        }
+    }
+
+    /// Changes the periods. The current period ends at `now` and the next one begins.
+    pub fn set_periods(
+        &mut self,
+        now: Time,
+        calculate_period: Time,
+        aggregate_period: Time,
+    ) -> Result<(), &'static str>
+    {
+        if calculate_period <= aggregate_period || aggregate_period <= self.reveal_period
+        {
+            return Err("Wrong period params.");
+        }
+
+        self.first_period = self.get_period(now) + One::one();
+        self.start = now;
+        self.calculate_period = calculate_period;
+        self.aggregate_period = aggregate_period;
+        Ok(())
     }
 
     pub fn is_aggregate_time(&self, now: Time) -> bool
     {
-        let next_period = self.get_period(now) + One::one();
-        let next_period_begin = self.start + next_period * self.calculate_period;
+        let next_period_begin = self.get_period_begin(self.get_period(now) + One::one());
 
         (next_period_begin - now) <= self.aggregate_period
     }
 
+    /// Time until the next aggregation window begins, zero in the window.
+    pub fn get_time_to_aggregate(&self, now: Time) -> Time
+    {
+        if self.is_aggregate_time(now)
+        {
+            return Zero::zero();
+        }
+
+        let next_period_begin = self.get_period_begin(self.get_period(now) + One::one());
+        next_period_begin - self.aggregate_period - now
+    }
+
+    /// Sets the reveal sub-window at the end of the aggregation window, zero disables it.
+    pub fn set_reveal_period(&mut self, reveal_period: Time) -> Result<(), &'static str>
+    {
+        if reveal_period >= self.aggregate_period
+        {
+            return Err("Reveal period must be shorter than aggregate period.");
+        }
+
+        self.reveal_period = reveal_period;
+        Ok(())
+    }
+
+    pub fn is_reveal_enabled(&self) -> bool
+    {
+        self.reveal_period > Zero::zero()
+    }
+
+    /// Aggregation window without the reveal sub-window.
+    pub fn is_commit_time(&self, now: Time) -> bool
+    {
+        self.is_aggregate_time(now) && !self.is_reveal_time(now)
+    }
+
+    pub fn is_reveal_time(&self, now: Time) -> bool
+    {
+        let next_period_begin = self.get_period_begin(self.get_period(now) + One::one());
+
+        (next_period_begin - now) <= self.reveal_period
+    }
+
+    /// Values are calculated once per period, after the aggregation window is closed,
+    /// so all values committed in the window are taken into account.
     pub fn is_calculate_time(&self, last_update_time: Option<Time>, now: Time) -> bool
     {
+        if self.is_aggregate_time(now)
+        {
+            return false;
+        }
+
         match last_update_time
         {
             Some(last_changed) => self.get_period(now) > self.get_period(last_changed),
@@ -69,6 +206,27 @@ impl<
         }
     }
 
+    /// Number of aggregation windows between the window of `last_changed` and
+    /// the last window started before `now`.
+    pub fn get_window_age(&self, last_changed: Time, now: Time) -> Time
+    {
+        let changed = self.get_period(last_changed);
+        let mut current = self.get_period(now);
+        if !self.is_aggregate_time(now) && current > changed
+        {
+            current = current - One::one();
+        }
+
+        if current > changed
+        {
+            current - changed
+        }
+        else
+        {
+            Zero::zero()
+        }
+    }
+
     pub fn update_source_time(&mut self, now: Time)
     {
         self.last_sources_update = now;
@@ -97,6 +255,37 @@ mod tests
         assert_eq!(handler.get_period(121), 2);
     }
 
+    #[test]
+    fn get_period_begin()
+    {
+        let handler = PeriodHandler::new(100, 10, 5).unwrap();
+
+        assert_eq!(handler.get_period_begin(0), 100);
+        assert_eq!(handler.get_period_begin(3), 130);
+        assert_eq!(handler.get_period_begin(handler.get_period(125)), 120);
+    }
+
+    #[test]
+    fn set_periods()
+    {
+        let mut handler = PeriodHandler::new(100, 10, 5).unwrap();
+
+        assert!(handler.set_periods(123, 5, 5).is_err());
+        assert_eq!(handler.get_period(123), 2);
+
+        assert!(handler.set_periods(123, 20, 10).is_ok());
+        assert_eq!(handler.get_period(122), 2);
+        assert_eq!(handler.get_period(123), 3);
+        assert_eq!(handler.get_period(142), 3);
+        assert_eq!(handler.get_period(143), 4);
+        assert_eq!(handler.get_period_begin(4), 143);
+        assert_eq!(handler.get_period_begin(2), 123);
+
+        assert!(handler.is_calculate_time(Some(121), 123));
+        assert!(!handler.is_aggregate_time(132));
+        assert!(handler.is_aggregate_time(133));
+    }
+
     #[test]
     fn is_aggregate_time()
     {
@@ -106,6 +295,41 @@ mod tests
         (205..=209).for_each(|now| assert!(handler.is_aggregate_time(now)));
     }
 
+    #[test]
+    fn get_time_to_aggregate()
+    {
+        let handler = PeriodHandler::new(100, 10, 5).unwrap();
+
+        assert_eq!(handler.get_time_to_aggregate(100), 5);
+        assert_eq!(handler.get_time_to_aggregate(104), 1);
+        assert_eq!(handler.get_time_to_aggregate(105), 0);
+        assert_eq!(handler.get_time_to_aggregate(109), 0);
+        assert_eq!(handler.get_time_to_aggregate(110), 5);
+    }
+
+    #[test]
+    fn reveal_period()
+    {
+        let mut handler = PeriodHandler::new(100, 10, 5).unwrap();
+        assert!(!handler.is_reveal_enabled());
+        (205..=209).for_each(|now| assert!(handler.is_commit_time(now)));
+
+        assert!(handler.set_reveal_period(5).is_err());
+        assert!(handler.set_reveal_period(2).is_ok());
+        assert!(handler.is_reveal_enabled());
+
+        assert!(!handler.is_commit_time(204));
+        (205..=207).for_each(|now| assert!(handler.is_commit_time(now)));
+        (208..=209).for_each(|now| {
+            assert!(!handler.is_commit_time(now));
+            assert!(handler.is_reveal_time(now));
+        });
+        assert!(!handler.is_reveal_time(207));
+
+        assert!(handler.set_periods(210, 20, 2).is_err());
+        assert!(handler.set_periods(210, 20, 3).is_ok());
+    }
+
     #[test]
     fn is_calculate_time()
     {
@@ -113,7 +337,34 @@ mod tests
 
         assert!(handler.is_calculate_time(None, 100));
         assert!(handler.is_calculate_time(Some(100), 110));
+        assert!(handler.is_calculate_time(Some(100), 114));
         assert!(!handler.is_calculate_time(Some(100), 101));
+        assert!(!handler.is_calculate_time(None, 105));
+        assert!(!handler.is_calculate_time(Some(100), 115));
+    }
+
+    #[test]
+    fn get_window_age()
+    {
+        let handler = PeriodHandler::new(100, 10, 5).unwrap();
+
+        assert_eq!(handler.get_window_age(105, 109), 0);
+        assert_eq!(handler.get_window_age(105, 114), 0);
+        assert_eq!(handler.get_window_age(105, 115), 1);
+        assert_eq!(handler.get_window_age(105, 124), 1);
+        assert_eq!(handler.get_window_age(105, 135), 3);
+    }
+
+    #[test]
+    fn staleness_policy()
+    {
+        use super::StalenessPolicy;
+
+        assert!(StalenessPolicy::Any.is_actual(100u32));
+        assert!(StalenessPolicy::CurrentWindow.is_actual(0u32));
+        assert!(!StalenessPolicy::CurrentWindow.is_actual(1u32));
+        assert!(StalenessPolicy::MaxAge(2).is_actual(2u32));
+        assert!(!StalenessPolicy::MaxAge(2).is_actual(3u32));
     }
 
     #[test]
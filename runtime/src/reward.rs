@@ -0,0 +1,136 @@
+use rstd::prelude::*;
+
+use codec::{Decode, Encode};
+use sr_primitives::{traits::SimpleArithmetic, Permill};
+
+use crate::slashing::is_deviating;
+
+/// Rewards of sources that commit in the aggregation window values close to the calculated one.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RewardRule<AssetId, Balance>
+{
+    /// Asset of the reward pool.
+    pub asset_id: AssetId,
+    /// Paid from the pool for every period. It is split equally between the assets
+    /// of the oracle, and the part of an asset between its rewarded sources.
+    pub per_period: Balance,
+    /// Maximum deviation of a rewarded value from the calculated one.
+    pub max_deviation: Permill,
+}
+
+impl<AssetId, Balance: SimpleArithmetic + Copy> RewardRule<AssetId, Balance>
+{
+    pub fn is_rewarded<V: SimpleArithmetic + Copy>(&self, value: V, calculated: V) -> bool
+    {
+        !is_deviating(value, calculated, self.max_deviation)
+    }
+
+    /// Reward of every source for one asset, limited by the `pool`.
+    pub fn get_source_reward(
+        &self,
+        assets_count: usize,
+        sources_count: usize,
+        pool: Balance,
+    ) -> Balance
+    {
+        if assets_count == 0 || sources_count == 0
+        {
+            return Balance::zero();
+        }
+
+        let asset_reward = self.per_period / Balance::from(assets_count as u32);
+        rstd::cmp::min(asset_reward, pool) / Balance::from(sources_count as u32)
+    }
+}
+
+/// Splits the `reward` of a source between the source and the voters that backed it.
+/// The source keeps `commission` and the rounding remainder, the rest is shared
+/// pro rata to the `backers` stakes. Returns the part of the source and the parts of the backers.
+pub fn split_reward<AccountId, Balance: SimpleArithmetic + Copy>(
+    reward: Balance,
+    commission: Permill,
+    backers: Vec<(AccountId, Balance)>,
+) -> (Balance, Vec<(AccountId, Balance)>)
+{
+    let total_stake = backers.iter().fold(Balance::zero(), |total, (_, stake)| {
+        total.saturating_add(*stake)
+    });
+    if total_stake.is_zero()
+    {
+        return (reward, Vec::new());
+    }
+
+    let shared = reward - commission * reward;
+    let shares: Vec<(AccountId, Balance)> = backers
+        .into_iter()
+        .map(|(backer, stake)| {
+            let share = shared
+                .checked_mul(&stake)
+                .map_or(shared / total_stake * stake, |product| {
+                    product / total_stake
+                });
+            (backer, share)
+        })
+        .filter(|(_, share)| !share.is_zero())
+        .collect();
+    let paid = shares.iter().fold(Balance::zero(), |paid, (_, share)| {
+        paid.saturating_add(*share)
+    });
+
+    (reward.saturating_sub(paid), shares)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{split_reward, RewardRule};
+    use sr_primitives::Permill;
+
+    #[test]
+    fn source_reward()
+    {
+        let rule = RewardRule {
+            asset_id: 0u64,
+            per_period: 60u128,
+            max_deviation: Permill::from_percent(10),
+        };
+
+        assert!(rule.is_rewarded(110u128, 100));
+        assert!(!rule.is_rewarded(111u128, 100));
+
+        assert_eq!(rule.get_source_reward(2, 3, 1000), 10);
+        assert_eq!(rule.get_source_reward(2, 3, 20), 6);
+        assert_eq!(rule.get_source_reward(2, 0, 1000), 0);
+        assert_eq!(rule.get_source_reward(0, 3, 1000), 0);
+    }
+
+    #[test]
+    fn split_source_reward()
+    {
+        assert_eq!(
+            split_reward(
+                20u128,
+                Permill::from_percent(20),
+                vec![(1u64, 100u128), (3, 300)]
+            ),
+            (4, vec![(1, 4), (3, 12)])
+        );
+        assert_eq!(
+            split_reward(10u128, Permill::zero(), vec![(1u64, 1u128), (2, 1), (3, 1)]),
+            (1, vec![(1, 3), (2, 3), (3, 3)])
+        );
+        assert_eq!(
+            split_reward(
+                10u128,
+                Permill::from_percent(50),
+                vec![(1u64, 1u128), (2, 100)]
+            ),
+            (6, vec![(2, 4)])
+        );
+        assert_eq!(
+            split_reward(10u128, Permill::zero(), Vec::<(u64, u128)>::new()),
+            (10, vec![])
+        );
+    }
+}
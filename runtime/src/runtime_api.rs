@@ -0,0 +1,45 @@
+//! Runtime APIs for reading the state of oracles and tables without decoding their storage.
+
+use codec::Codec;
+use rstd::prelude::*;
+
+sr_api::decl_runtime_apis! {
+    pub trait OracleApi<AccountId, OracleId, ExternalValue, Moment>
+    where
+        AccountId: Codec,
+        OracleId: Codec,
+        ExternalValue: Codec,
+        Moment: Codec,
+    {
+        /// Ids and names of all oracles.
+        fn get_oracles() -> Vec<(OracleId, Vec<u8>)>;
+        /// Calculated values of all assets with their metadata, as `(asset_id, value)` pairs.
+        fn get_values(oracle_id: OracleId) -> Vec<(u8, ExternalValue)>;
+        /// Assets of the oracle as `(asset_id, name, deprecated)`.
+        fn get_assets(oracle_id: OracleId) -> Vec<(u8, Vec<u8>, bool)>;
+        /// Sources that can commit in the current aggregation window.
+        fn get_sources(oracle_id: OracleId) -> Vec<AccountId>;
+        /// Number of the current period, `None` if there is no open oracle.
+        fn get_current_period(oracle_id: OracleId) -> Option<Moment>;
+        fn is_aggregate_time(oracle_id: OracleId) -> bool;
+        /// Time until the next aggregation window, zero in the window.
+        fn get_time_to_aggregate(oracle_id: OracleId) -> Option<Moment>;
+        /// Whether the account is a source of the oracle and can commit now.
+        fn can_commit(oracle_id: OracleId, account: AccountId) -> bool;
+    }
+
+    pub trait TablescoreApi<AccountId, TableId, TargetType, Balance>
+    where
+        AccountId: Codec,
+        TableId: Codec,
+        TargetType: Codec,
+        Balance: Codec,
+    {
+        /// Targets of the table head with their stakes, the largest stake first.
+        fn get_head(table_id: TableId) -> Vec<(TargetType, Balance)>;
+        /// All voted targets of the table with their stakes, the largest stake first.
+        fn get_leaderboard(table_id: TableId) -> Vec<(TargetType, Balance)>;
+        /// Current vote of the voter as `(target, balance)`.
+        fn get_vote(table_id: TableId, voter: AccountId) -> Option<(TargetType, Balance)>;
+    }
+}
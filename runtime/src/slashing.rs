@@ -0,0 +1,64 @@
+use codec::{Decode, Encode};
+use sr_primitives::{traits::SimpleArithmetic, Permill};
+
+/// Slashing of sources that commit values deviating from the calculated one.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SlashRule
+{
+    /// Maximum deviation from the calculated value, as a part of it.
+    pub max_deviation: Permill,
+    /// Part of the stake of the source slashed if any of its values committed in the period
+    /// deviates. A source is slashed at most once per period.
+    pub slash: Permill,
+}
+
+/// Whether `value` differs from `calculated` by more than `max_deviation` part of `calculated`.
+pub fn is_deviating<V: SimpleArithmetic + Copy>(
+    value: V,
+    calculated: V,
+    max_deviation: Permill,
+) -> bool
+{
+    let deviation = if value > calculated
+    {
+        value - calculated
+    }
+    else
+    {
+        calculated - value
+    };
+
+    deviation > max_deviation * calculated
+}
+
+impl SlashRule
+{
+    pub fn is_deviating<V: SimpleArithmetic + Copy>(&self, value: V, calculated: V) -> bool
+    {
+        is_deviating(value, calculated, self.max_deviation)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::SlashRule;
+    use sr_primitives::Permill;
+
+    #[test]
+    fn is_deviating()
+    {
+        let rule = SlashRule {
+            max_deviation: Permill::from_percent(10),
+            slash: Permill::from_percent(50),
+        };
+
+        assert!(!rule.is_deviating(110u128, 100));
+        assert!(!rule.is_deviating(90u128, 100));
+        assert!(rule.is_deviating(111u128, 100));
+        assert!(rule.is_deviating(89u128, 100));
+        assert!(rule.is_deviating(1u128, 0));
+        assert!(!rule.is_deviating(0u128, 0));
+    }
+}
@@ -1,12 +1,13 @@
 use rstd::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
 
-use support::{decl_event, decl_module, decl_storage, dispatch::Result, Parameter};
+use support::{decl_error, decl_event, decl_module, decl_storage, Parameter};
 
 use codec::{Decode, Encode};
 use core::cmp::{Ord, Ordering, PartialOrd};
 use rstd::prelude::*;
 use rstd::result;
-use sr_primitives::traits::{CheckedAdd, Member, One, SimpleArithmetic, Zero};
+use sr_primitives::traits::{CheckedAdd, Member, One, Saturating, SimpleArithmetic, Zero};
+use sr_primitives::Permill;
 
 use system::ensure_signed;
 
@@ -30,6 +31,8 @@ pub struct Record<T: Trait>
 {
     target: T::TargetType,
     balance: Balance<T>,
+    /// Keeps equal votes of different voters apart in `Table::scores`.
+    voter: AccountId<T>,
 }
 
 impl<T: Trait> Ord for Record<T>
@@ -38,7 +41,11 @@ impl<T: Trait> Ord for Record<T>
     {
         match self.balance.cmp(&other.balance)
         {
-            Ordering::Equal => self.target.cmp(&other.target),
+            Ordering::Equal => match self.target.cmp(&other.target)
+            {
+                Ordering::Equal => self.voter.cmp(&other.voter),
+                ordering => ordering,
+            },
             Ordering::Greater => Ordering::Less,
             Ordering::Less => Ordering::Greater,
         }
@@ -60,6 +67,7 @@ impl<T: Trait> Default for Record<T>
         Record {
             target: T::TargetType::default(),
             balance: Balance::<T>::default(),
+            voter: AccountId::<T>::default(),
         }
     }
 }
@@ -75,6 +83,19 @@ pub struct Table<T: Trait>
     pub reserved: BTreeMap<AccountId<T>, Record<T>>,
 }
 
+impl<T: Trait> Table<T>
+{
+    pub fn get_target_stake(&self, target: &T::TargetType) -> Balance<T>
+    {
+        self.scores
+            .iter()
+            .filter(|record| record.target == *target)
+            .fold(Zero::zero(), |stake: Balance<T>, record| {
+                stake.saturating_add(record.balance)
+            })
+    }
+}
+
 impl<T: Trait> Default for Table<T>
 {
     fn default() -> Self
@@ -96,15 +117,28 @@ decl_storage! {
     }
 }
 
+decl_error! {
+    /// Errors of the tablescore module.
+    pub enum Error
+    {
+        /// There is no table with this id.
+        TableNotFound,
+        /// No more ids for tables.
+        TableIdOverflow,
+    }
+}
+
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error;
+
         fn deposit_event() = default;
 
         pub fn create_table(
             origin,
             vote_asset: AssetId<T>,
             head_count: u8,
-            name: Option<Vec<u8>>) -> Result
+            name: Option<Vec<u8>>) -> result::Result<(), Error>
         {
             let _ = ensure_signed(origin)?;
             Self::create(vote_asset, head_count, name)?;
@@ -115,16 +149,27 @@ decl_module! {
             origin,
             table_id: T::TableId,
             balance: Balance<T>,
-            target: T::TargetType) -> Result
+            target: T::TargetType) -> result::Result<(), Error>
         {
             let voter = ensure_signed(origin)?;
+            if !Scores::<T>::exists(&table_id) {
+                return Err(Error::TableNotFound);
+            }
             let table = Scores::<T>::get(&table_id);
 
-            let new_record = Record { target, balance };
+            let new_record = Record { target, balance, voter: voter.clone() };
             let old_record = table.reserved.get(&voter);
 
             Self::rereserve(&voter, &table.vote_asset, old_record, &new_record)?;
 
+            let head = Self::get_head(&table_id);
+            let event = match old_record {
+                Some(record) if new_record.balance.is_zero() =>
+                    Some(RawEvent::Unvoted(table_id, voter.clone(), record.target.clone(), record.balance)),
+                _ if new_record.balance.is_zero() => None,
+                _ => Some(RawEvent::Voted(table_id, voter.clone(), new_record.target.clone(), new_record.balance)),
+            };
+
             Scores::<T>::mutate(&table_id, |table| {
                 table.reserved.remove(&voter);
                 if let Some(record) = old_record { table.scores.remove(record); }
@@ -135,12 +180,16 @@ decl_module! {
                 }
             });
 
+            if let Some(event) = event {
+                Self::deposit_event(event);
+            }
+            Self::deposit_head_change(&table_id, head);
             Ok(())
         }
 
         pub fn unvote(
             origin,
-            table_id: T::TableId) -> Result
+            table_id: T::TableId) -> result::Result<(), Error>
         {
             Self::vote(origin, table_id, Zero::zero(), T::TargetType::default())
         }
@@ -151,8 +200,17 @@ decl_event!(
     pub enum Event<T>
     where
         AccountId = <T as system::Trait>::AccountId,
+        TableId = <T as Trait>::TableId,
+        TargetType = <T as Trait>::TargetType,
+        Balance = <T as assets::Trait>::Balance,
     {
-        Voted(AccountId),
+        TableCreated(TableId),
+        /// The voter reserved the balance for the target, replacing its previous vote in the table.
+        Voted(TableId, AccountId, TargetType, Balance),
+        /// The voter withdrew its vote for the target and the balance was unreserved.
+        Unvoted(TableId, AccountId, TargetType, Balance),
+        /// Targets at the head of the table changed, the new head is in order of stake.
+        HeadChanged(TableId, Vec<TargetType>),
     }
 );
 
@@ -162,7 +220,7 @@ impl<T: Trait> Module<T>
         vote_asset: AssetId<T>,
         head_count: u8,
         name: Option<Vec<u8>>,
-    ) -> result::Result<T::TableId, &'static str>
+    ) -> result::Result<T::TableId, Error>
     {
         let id = Self::pop_new_table_id()?;
         Scores::<T>::insert(
@@ -175,13 +233,118 @@ impl<T: Trait> Module<T>
                 reserved: BTreeMap::new(),
             },
         );
+        Self::deposit_event(RawEvent::TableCreated(id));
 
         Ok(id)
     }
 
-    fn pop_new_table_id() -> result::Result<T::TableId, &'static str>
+    /// Removes the table and unreserves the assets of all its voters.
+    pub fn remove_table(table_id: &T::TableId)
     {
-        let mut result = Err("Unknown error");
+        let table = Scores::<T>::take(table_id);
+        table.reserved.iter().for_each(|(voter, record)| {
+            assets::Module::<T>::unreserve(&table.vote_asset, voter, record.balance);
+        });
+    }
+
+    /// Slashes `fraction` of the vote of `voter`. Slashed assets are moved to
+    /// the free balance of `destination` or burned. Returns the amount actually slashed,
+    /// which is less if the reserved balance of the voter is lower than its vote.
+    pub fn slash_voter(
+        table_id: &T::TableId,
+        voter: &AccountId<T>,
+        fraction: Permill,
+        destination: Option<&AccountId<T>>,
+    ) -> Balance<T>
+    {
+        let table = Scores::<T>::get(table_id);
+        let record = match table.reserved.get(voter)
+        {
+            Some(record) => record.clone(),
+            None => return Zero::zero(),
+        };
+
+        let amount = fraction * record.balance;
+        if amount.is_zero()
+        {
+            return amount;
+        }
+
+        let remaining = match destination
+        {
+            Some(destination) => assets::Module::<T>::repatriate_reserved(
+                &table.vote_asset,
+                voter,
+                destination,
+                amount,
+            ),
+            None => assets::Module::<T>::slash_reserved(&table.vote_asset, voter, amount)
+                .unwrap_or_else(Zero::zero),
+        };
+        let amount = amount - remaining;
+        if amount.is_zero()
+        {
+            return amount;
+        }
+
+        let head = Self::get_head(table_id);
+        Scores::<T>::mutate(table_id, |table| {
+            table.scores.remove(&record);
+            table.reserved.remove(voter);
+
+            let new_record = Record {
+                target: record.target.clone(),
+                balance: record.balance - amount,
+                voter: voter.clone(),
+            };
+            if !new_record.balance.is_zero()
+            {
+                table.reserved.insert(voter.clone(), new_record.clone());
+                table.scores.insert(new_record);
+            }
+        });
+        Self::deposit_head_change(table_id, head);
+
+        amount
+    }
+
+    /// Slashes `fraction` of the votes of all voters for `target`, so they share the loss
+    /// in proportion to their reserved balances. Returns the total slashed amount.
+    pub fn slash_target(
+        table_id: &T::TableId,
+        target: &T::TargetType,
+        fraction: Permill,
+        destination: Option<&AccountId<T>>,
+    ) -> Balance<T>
+    {
+        Self::get_backers(table_id, target).into_iter().fold(
+            Zero::zero(),
+            |total: Balance<T>, (voter, _)| {
+                total.saturating_add(Self::slash_voter(table_id, &voter, fraction, destination))
+            },
+        )
+    }
+
+    pub fn set_head_count(table_id: &T::TableId, head_count: u8)
+    {
+        let head = Self::get_head(table_id);
+        Scores::<T>::mutate(table_id, |table| table.head_count = head_count);
+        Self::deposit_head_change(table_id, head);
+    }
+
+    /// Deposits `HeadChanged` if the head of the table differs from `old_head`.
+    fn deposit_head_change(table_id: &T::TableId, old_head: Vec<T::TargetType>)
+    {
+        let head = Self::get_head(table_id);
+        if head != old_head
+        {
+            Self::deposit_event(RawEvent::HeadChanged(*table_id, head));
+        }
+    }
+
+    fn pop_new_table_id() -> result::Result<T::TableId, Error>
+    {
+        let mut result = Err(Error::TableIdOverflow);
 
         TableScoreIdSequence::<T>::mutate(|id| match id.checked_add(&One::one())
         {
@@ -192,7 +355,7 @@ impl<T: Trait> Module<T>
             }
             None =>
             {
-                result = Err("T::TableId overflow. Can't get next id.");
+                result = Err(Error::TableIdOverflow);
             }
         });
 
@@ -204,7 +367,7 @@ impl<T: Trait> Module<T>
         asset_id: &AssetId<T>,
         old_record: Option<&Record<T>>,
         new_record: &Record<T>,
-    ) -> Result
+    ) -> result::Result<(), Error>
     {
         match old_record
         {
@@ -237,26 +400,85 @@ impl<T: Trait> Module<T>
         Ok(())
     }
 
+    /// Targets with the largest stakes, summed over all votes for the target.
     pub fn get_head(table_id: &T::TableId) -> Vec<T::TargetType>
     {
-        let table = Scores::<T>::get(table_id);
-        table
+        Self::get_head_with_stake(table_id)
+            .into_iter()
+            .map(|(target, _)| target)
+            .collect()
+    }
+
+    /// All voted targets of the table with their stakes, the largest stake first.
+    pub fn get_leaderboard(table_id: &T::TableId) -> Vec<(T::TargetType, Balance<T>)>
+    {
+        Self::get_ranking(&Scores::<T>::get(table_id))
+    }
+
+    /// Current vote of the voter as `(target, balance)`.
+    pub fn get_vote(
+        table_id: &T::TableId,
+        voter: &AccountId<T>,
+    ) -> Option<(T::TargetType, Balance<T>)>
+    {
+        Scores::<T>::get(table_id)
+            .reserved
+            .get(voter)
+            .map(|record| (record.target.clone(), record.balance))
+    }
+
+    /// Every voted target once with its summed stake, the largest stake first.
+    /// Targets with equal stakes are ordered by their largest votes.
+    fn get_ranking(table: &Table<T>) -> Vec<(T::TargetType, Balance<T>)>
+    {
+        let mut targets = BTreeSet::new();
+        let mut ranking: Vec<(T::TargetType, Balance<T>)> = table
             .scores
             .iter()
             .map(|record| record.target.clone())
+            .filter(|target| targets.insert(target.clone()))
+            .map(|target| {
+                let stake = table.get_target_stake(&target);
+                (target, stake)
+            })
+            .collect();
+        ranking.sort_by(|(_, left), (_, right)| right.cmp(left));
+        ranking
+    }
+
+    pub fn get_head_with_stake(table_id: &T::TableId) -> Vec<(T::TargetType, Balance<T>)>
+    {
+        let table = Scores::<T>::get(table_id);
+        Self::get_ranking(&table)
+            .into_iter()
             .take(table.head_count as usize)
             .collect()
     }
+
+    /// Voters of the table that vote for `target`, with their reserved balances.
+    pub fn get_backers(
+        table_id: &T::TableId,
+        target: &T::TargetType,
+    ) -> Vec<(AccountId<T>, Balance<T>)>
+    {
+        Scores::<T>::get(table_id)
+            .reserved
+            .into_iter()
+            .filter(|(_, record)| record.target == *target)
+            .map(|(voter, record)| (voter, record.balance))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests
 {
     use crate::mock::{
-        new_test_ext, Origin, TablescoreModule, Test, ALICE, ASSET_ID, BALANCE, BOB, CAROL,
+        new_test_ext, AssetsModule, Origin, Permill, TablescoreModule, Test, ALICE, ASSET_ID,
+        BALANCE, BOB, CAROL,
     };
 
-    use crate::tablescore::Table;
+    use crate::tablescore::{Error, Table};
     use rstd::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
 
     fn get_test_table() -> Table<Test>
@@ -306,6 +528,10 @@ mod tests
             .is_ok());
 
             assert!(TablescoreModule::vote(Origin::signed(ALICE), id, BALANCE + 1, 1).is_err());
+            assert_eq!(
+                TablescoreModule::vote(Origin::signed(ALICE), id + 1, 1, 1),
+                Err(Error::TableNotFound)
+            );
         });
     }
 
@@ -334,4 +560,192 @@ mod tests
             assert_eq!(TablescoreModule::get_head(&id), vec![3, 1]);
         });
     }
+
+    #[test]
+    fn head_without_repeated_targets_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            assert!(
+                TablescoreModule::create_table(Origin::signed(ALICE), ASSET_ID, 2, None).is_ok()
+            );
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 300u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 200u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(CAROL), id, 100u128, 2).is_ok());
+
+            assert_eq!(TablescoreModule::get_head(&id), vec![1, 2]);
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(1, 500u128), (2, 100u128)]
+            );
+        });
+    }
+
+    #[test]
+    fn slash_voter_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            assert!(
+                TablescoreModule::create_table(Origin::signed(ALICE), ASSET_ID, 2, None).is_ok()
+            );
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 100u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 10u128, 2).is_ok());
+
+            assert_eq!(
+                TablescoreModule::slash_voter(&id, &ALICE, Permill::from_percent(30), Some(&CAROL)),
+                30
+            );
+            assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &ALICE), 70);
+            assert_eq!(AssetsModule::free_balance(&ASSET_ID, &CAROL), BALANCE + 30);
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(1, 70u128), (2, 10u128)]
+            );
+
+            assert_eq!(
+                TablescoreModule::slash_voter(&id, &BOB, Permill::from_percent(100), None),
+                10
+            );
+            assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &BOB), 0);
+            assert_eq!(AssetsModule::free_balance(&ASSET_ID, &BOB), BALANCE - 10);
+            assert_eq!(TablescoreModule::get_head(&id), vec![1]);
+            assert!(TablescoreModule::scores(&id).reserved.get(&BOB).is_none());
+
+            assert_eq!(
+                TablescoreModule::slash_voter(&id, &CAROL, Permill::from_percent(100), None),
+                0
+            );
+        });
+    }
+
+    #[test]
+    fn head_with_stake_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            let table = get_test_table();
+            assert!(TablescoreModule::create_table(
+                Origin::signed(ALICE),
+                ASSET_ID,
+                table.head_count,
+                table.name.clone()
+            )
+            .is_ok());
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 3u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 2u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(CAROL), id, 4u128, 2).is_ok());
+
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(1, 5u128), (2, 4u128)]
+            );
+        });
+    }
+
+    #[test]
+    fn backers_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            assert!(
+                TablescoreModule::create_table(Origin::signed(ALICE), ASSET_ID, 2, None).is_ok()
+            );
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 100u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 300u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(CAROL), id, 10u128, 2).is_ok());
+
+            assert_eq!(TablescoreModule::get_head(&id), vec![1, 2]);
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(1, 400u128), (2, 10u128)]
+            );
+            assert_eq!(
+                TablescoreModule::get_backers(&id, &1),
+                vec![(ALICE, 100u128), (BOB, 300u128)]
+            );
+            assert!(TablescoreModule::get_backers(&id, &3).is_empty());
+        });
+    }
+
+    #[test]
+    fn leaderboard_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            assert!(
+                TablescoreModule::create_table(Origin::signed(ALICE), ASSET_ID, 1, None).is_ok()
+            );
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 100u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 300u128, 2).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(CAROL), id, 10u128, 1).is_ok());
+
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(2, 300u128)]
+            );
+            assert_eq!(
+                TablescoreModule::get_leaderboard(&id),
+                vec![(2, 300u128), (1, 110u128)]
+            );
+            assert_eq!(TablescoreModule::get_vote(&id, &ALICE), Some((1, 100u128)));
+
+            assert!(TablescoreModule::unvote(Origin::signed(ALICE), id).is_ok());
+            assert_eq!(TablescoreModule::get_vote(&id, &ALICE), None);
+            assert_eq!(
+                TablescoreModule::get_leaderboard(&id),
+                vec![(2, 300u128), (1, 10u128)]
+            );
+
+            // The head and the leaderboard follow the summed stake, not the largest vote
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 295u128, 1).is_ok());
+            assert_eq!(TablescoreModule::get_head(&id), vec![1]);
+            assert_eq!(
+                TablescoreModule::get_leaderboard(&id),
+                vec![(1, 305u128), (2, 300u128)]
+            );
+        });
+    }
+
+    #[test]
+    fn slash_target_tablescore()
+    {
+        new_test_ext().execute_with(|| {
+            let id = TablescoreModule::next_tablescore_id();
+            assert!(
+                TablescoreModule::create_table(Origin::signed(ALICE), ASSET_ID, 2, None).is_ok()
+            );
+
+            assert!(TablescoreModule::vote(Origin::signed(ALICE), id, 100u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(BOB), id, 100u128, 1).is_ok());
+            assert!(TablescoreModule::vote(Origin::signed(CAROL), id, 300u128, 1).is_ok());
+            assert_eq!(TablescoreModule::scores(&id).scores.len(), 3);
+
+            assert_eq!(
+                TablescoreModule::slash_target(&id, &1, Permill::from_percent(10), None),
+                50
+            );
+            assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &ALICE), 90);
+            assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &BOB), 90);
+            assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &CAROL), 270);
+            assert_eq!(
+                TablescoreModule::get_head_with_stake(&id),
+                vec![(1, 450u128)]
+            );
+            assert_eq!(
+                TablescoreModule::get_backers(&id, &1),
+                vec![(ALICE, 90u128), (BOB, 90u128), (CAROL, 270u128)]
+            );
+
+            assert_eq!(
+                TablescoreModule::slash_target(&id, &2, Permill::from_percent(10), None),
+                0
+            );
+        });
+    }
 }
@@ -1,4 +1,22 @@
-use crate::mock::{new_test_ext, AssetsVec, OracleModule, Origin, ALICE, ASSET_ID};
+use codec::{Decode, Encode};
+use primitives::offchain::{
+    testing::{PendingRequest, TestOffchainExt, TestTransactionPoolExt},
+    OffchainExt, StorageKind, TransactionPoolExt,
+};
+use sr_primitives::testing::UintAuthorityId;
+use sr_primitives::traits::{BlakeTwo256, Hash, OnFinalize};
+use support::StorageMap;
+
+use crate::mock::{
+    new_test_ext, AccountId, AggregatorType, AssetsModule, AssetsVec, Extrinsic, OracleModule,
+    Origin, OutlierFilter, Permill, RewardRule, Scores, SlashRule, StalenessPolicy, System,
+    TablescoreModule, Test, TestEvent, TimestampModule, ALICE, ASSET_ID, BOB, CAROL, TREASURY,
+};
+use crate::module::{Call, Error, Oracles, RawEvent};
+use crate::offchain::{Endpoint, ENDPOINTS_KEY};
+use crate::tablescore::RawEvent as TablescoreEvent;
+
+const SOURCES: [AccountId; 3] = [ALICE, BOB, CAROL];
 
 #[inline]
 fn to_raw(data: &&'static str) -> Vec<u8>
@@ -11,17 +29,951 @@ fn create_oracle()
 {
     new_test_ext().execute_with(|| {
         let id = OracleModule::next_oracle_id();
-        let oracle = OracleModule::create(
+        assert!(OracleModule::create(
             Origin::signed(ALICE),
             "test".to_owned().as_bytes().to_vec(),
             ASSET_ID,
             5,
             60,
             120,
+            AggregatorType::StakeWeightedMedian,
+            OutlierFilter::Mad(300),
+            StalenessPolicy::MaxAge(2),
             AssetsVec {
                 0: ["one", "two", "three"].iter().map(to_raw).collect(),
             },
+            Some(AssetsVec { 0: vec![5, 2, 3] }),
+        )
+        .is_ok());
+
+        let oracle = OracleModule::oracles(id);
+        assert_eq!(oracle.name, to_raw(&"test"));
+        assert_eq!(oracle.aggregator, AggregatorType::StakeWeightedMedian);
+        assert_eq!(oracle.outlier_filter, OutlierFilter::Mad(300));
+        assert_eq!(oracle.staleness, StalenessPolicy::MaxAge(2));
+        assert_eq!(oracle.get_assets_count(), 3);
+        assert_eq!(oracle.assets_threshold.0, vec![5, 2, 3]);
+        assert_eq!(OracleModule::next_oracle_id(), id + 1);
+
+        assert!(OracleModule::create(
+            Origin::signed(ALICE),
+            to_raw(&"wrong"),
+            ASSET_ID,
+            5,
+            60,
+            120,
+            AggregatorType::Median,
+            OutlierFilter::Disabled,
+            StalenessPolicy::Any,
+            AssetsVec {
+                0: ["one"].iter().map(to_raw).collect(),
+            },
+            Some(AssetsVec { 0: vec![6] }),
+        )
+        .is_err());
+        assert_eq!(OracleModule::next_oracle_id(), id + 1);
+    });
+}
+
+/// Creates an oracle with two assets, `calculate_period` 10 and `aggregate_period` 5,
+/// where every account of `SOURCES` votes for itself.
+fn create_oracle_with_sources() -> u64
+{
+    let id = OracleModule::next_oracle_id();
+    assert!(OracleModule::create(
+        Origin::signed(ALICE),
+        to_raw(&"test"),
+        ASSET_ID,
+        3,
+        5,
+        10,
+        AggregatorType::Median,
+        OutlierFilter::Disabled,
+        StalenessPolicy::CurrentWindow,
+        AssetsVec {
+            0: ["one", "two"].iter().map(to_raw).collect(),
+        },
+        None,
+    )
+    .is_ok());
+
+    let table = OracleModule::oracles(id).table;
+    for source in SOURCES.iter()
+    {
+        assert!(TablescoreModule::vote(Origin::signed(*source), table, 100, *source).is_ok());
+    }
+
+    id
+}
+
+fn commit_all(oracle_id: u64, now: u64, values: Vec<u128>)
+{
+    TimestampModule::set_timestamp(now);
+    for source in SOURCES.iter()
+    {
+        assert!(OracleModule::commit(
+            Origin::signed(*source),
+            oracle_id,
+            AssetsVec { 0: values.clone() }
+        )
+        .is_ok());
+    }
+}
+
+fn calculate(oracle_id: u64, now: u64, number: u8)
+{
+    TimestampModule::set_timestamp(now);
+    assert!(OracleModule::calculate(Origin::signed(ALICE), oracle_id, number).is_ok());
+}
+
+#[test]
+fn calculate_value()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+        calculate(id, 20, 1);
+
+        assert_eq!(
+            OracleModule::get_value(id, 0).and_then(|value| value.value),
+            Some(10)
+        );
+        assert_eq!(
+            OracleModule::get_value(id, 1).and_then(|value| value.value),
+            Some(20)
+        );
+        assert!(OracleModule::calculate(Origin::signed(ALICE), id, 0).is_err());
+    });
+}
+
+#[test]
+fn history()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+        commit_all(id, 25, vec![11, 21]);
+        calculate(id, 32, 0);
+
+        let value_at =
+            |moment| OracleModule::get_value_at(id, 0, moment).and_then(|value| value.value);
+        assert_eq!(value_at(19), None);
+        assert_eq!(value_at(20), Some(10));
+        assert_eq!(value_at(31), Some(10));
+        assert_eq!(value_at(32), Some(11));
+        assert_eq!(OracleModule::get_value_at(id, 1, 40), None);
+        assert_eq!(OracleModule::history_periods((id, 0)), vec![2, 3]);
+
+        commit_all(id, 35, vec![12, 22]);
+        calculate(id, 40, 0);
+
+        assert_eq!(OracleModule::history_periods((id, 0)), vec![3, 4]);
+        assert_eq!(OracleModule::values_history((id, 0, 2)), None);
+        assert_eq!(value_at(25), None);
+        assert_eq!(value_at(40), Some(12));
+    });
+}
+
+#[test]
+fn twap()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 0]);
+        calculate(id, 20, 0);
+        commit_all(id, 25, vec![20, 0]);
+        calculate(id, 32, 0);
+
+        assert_eq!(OracleModule::get_twap(id, 0, 20, 40), Some(14));
+        assert_eq!(OracleModule::get_twap(id, 0, 25, 35), Some(13));
+        assert_eq!(OracleModule::get_twap(id, 0, 0, 30), Some(10));
+        assert_eq!(OracleModule::get_twap(id, 0, 0, 20), None);
+        assert_eq!(OracleModule::get_twap(id, 0, 35, 35), None);
+        assert_eq!(OracleModule::get_twap(id, 1, 0, 40), None);
+
+        TimestampModule::set_timestamp(45);
+        assert_eq!(OracleModule::get_twap_by_periods(id, 0, 2), Some(14));
+        assert_eq!(OracleModule::get_twap_by_periods(id, 0, 1), Some(18));
+        assert_eq!(OracleModule::get_twap_by_periods(id + 1, 0, 1), None);
+    });
+}
+
+#[test]
+fn commit_sparse()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+        calculate(id, 20, 1);
+
+        TimestampModule::set_timestamp(25);
+        assert!(OracleModule::commit_sparse(Origin::signed(ALICE), id, vec![(2, 11)]).is_err());
+        assert!(OracleModule::commit_sparse(Origin::signed(ASSET_ID), id, vec![(0, 11)]).is_err());
+        for source in SOURCES.iter()
+        {
+            assert!(
+                OracleModule::commit_sparse(Origin::signed(*source), id, vec![(1, 21)]).is_ok()
+            );
+        }
+
+        let oracle = OracleModule::oracles(id);
+        assert!(oracle
+            .sources
+            .values()
+            .all(|assets| assets.0[0].value == Some(10) && assets.0[1].value == Some(21)));
+
+        calculate(id, 30, 1);
+        assert_eq!(
+            OracleModule::get_value(id, 1).and_then(|value| value.value),
+            Some(21)
+        );
+    });
+}
+
+#[test]
+fn calculate_without_stale_values()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+
+        TimestampModule::set_timestamp(25);
+        assert!(OracleModule::commit_sparse(Origin::signed(ALICE), id, vec![(0, 11)]).is_ok());
+
+        TimestampModule::set_timestamp(30);
+        assert_eq!(
+            OracleModule::calculate(Origin::signed(ALICE), id, 0),
+            Err(Error::TooFewCommittedValues)
+        );
+        assert_eq!(
+            OracleModule::get_value(id, 0).and_then(|value| value.value),
+            Some(10)
+        );
+    });
+}
+
+#[test]
+fn calculate_on_finalize()
+{
+    new_test_ext().execute_with(|| {
+        let first = create_oracle_with_sources();
+        let second = create_oracle_with_sources();
+
+        commit_all(first, 15, vec![10, 20]);
+        commit_all(second, 15, vec![30, 40]);
+
+        TimestampModule::set_timestamp(20);
+        OracleModule::on_finalize(1);
+        assert_eq!(
+            OracleModule::get_value(first, 0).and_then(|value| value.value),
+            Some(10)
+        );
+        assert_eq!(
+            OracleModule::get_value(first, 1).and_then(|value| value.value),
+            Some(20)
+        );
+        assert_eq!(
+            OracleModule::get_value(second, 0).and_then(|value| value.value),
+            None
+        );
+
+        OracleModule::on_finalize(2);
+        assert_eq!(
+            OracleModule::get_value(second, 0).and_then(|value| value.value),
+            Some(30)
+        );
+        assert_eq!(
+            OracleModule::get_value(second, 1).and_then(|value| value.value),
+            Some(40)
+        );
+        assert_eq!(
+            OracleModule::get_value_at(second, 1, 20).and_then(|value| value.value),
+            Some(40)
+        );
+        assert_eq!(OracleModule::next_calculated_oracle(), first);
+    });
+}
+
+#[test]
+fn calculate_after_aggregation_window()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let value = |number| OracleModule::get_value(id, number).and_then(|value| value.value);
+
+        commit_all(id, 15, vec![10, 20]);
+        OracleModule::on_finalize(1);
+        assert_eq!(value(0), None);
+        assert_eq!(
+            OracleModule::calculate(Origin::signed(ALICE), id, 0),
+            Err(Error::NotCalculateTime)
+        );
+
+        // Later commits of the window are taken into account
+        TimestampModule::set_timestamp(19);
+        for source in [ALICE, BOB].iter()
+        {
+            assert!(OracleModule::commit(
+                Origin::signed(*source),
+                id,
+                AssetsVec { 0: vec![30, 20] }
+            )
+            .is_ok());
+        }
+        OracleModule::on_finalize(2);
+        assert_eq!(value(0), None);
+
+        TimestampModule::set_timestamp(20);
+        OracleModule::on_finalize(3);
+        assert_eq!(value(0), Some(30));
+        assert_eq!(value(1), Some(20));
+        assert_eq!(OracleModule::last_calculate_period(id), Some(2));
+
+        // Too few values in the next window, the calculation fails once in the period
+        TimestampModule::set_timestamp(25);
+        assert!(
+            OracleModule::commit(Origin::signed(ALICE), id, AssetsVec { 0: vec![40, 50] }).is_ok()
+        );
+        TimestampModule::set_timestamp(30);
+        OracleModule::on_finalize(4);
+        assert_eq!(value(0), Some(30));
+        assert_eq!(OracleModule::last_calculate_period(id), Some(3));
+        assert!(OracleModule::oracles(id).is_calculate_time(0, 30));
+    });
+}
+
+#[test]
+fn owner_extrinsics()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let table = OracleModule::oracles(id).table;
+        assert_eq!(OracleModule::oracles(id).owner, ALICE);
+
+        assert_eq!(
+            OracleModule::set_sources_threshold(Origin::signed(BOB), id, 5),
+            Err(Error::NotOwner)
+        );
+        assert!(OracleModule::set_sources_threshold(Origin::signed(ALICE), id, 2).is_err());
+        assert!(OracleModule::set_sources_threshold(Origin::signed(ALICE), id, 5).is_ok());
+        assert_eq!(OracleModule::oracles(id).get_sources_threshold(), 5);
+        assert_eq!(TablescoreModule::scores(table).head_count, 5);
+
+        TimestampModule::set_timestamp(23);
+        assert_eq!(
+            OracleModule::set_periods(Origin::signed(ALICE), id, 10, 10),
+            Err(Error::WrongPeriods)
+        );
+        assert!(OracleModule::set_periods(Origin::signed(ALICE), id, 20, 10).is_ok());
+        assert_eq!(OracleModule::oracles(id).period_handler.get_period(23), 3);
+        assert_eq!(
+            OracleModule::oracles(id).period_handler.get_period_begin(4),
+            43
+        );
+
+        assert!(OracleModule::rename(Origin::signed(BOB), id, to_raw(&"other")).is_err());
+        assert!(OracleModule::rename(Origin::ROOT, id, to_raw(&"other")).is_ok());
+        assert_eq!(OracleModule::oracles(id).name, to_raw(&"other"));
+
+        assert!(
+            OracleModule::rename_asset(Origin::signed(ALICE), id, 2, to_raw(&"three")).is_err()
+        );
+        assert!(OracleModule::rename_asset(Origin::signed(ALICE), id, 1, to_raw(&"three")).is_ok());
+        assert_eq!(OracleModule::oracles(id).assets_name.0[1], to_raw(&"three"));
+
+        assert!(OracleModule::transfer_ownership(Origin::signed(BOB), id, BOB).is_err());
+        assert!(OracleModule::transfer_ownership(Origin::signed(ALICE), id, BOB).is_ok());
+        assert!(OracleModule::rename(Origin::signed(ALICE), id, to_raw(&"test")).is_err());
+        assert!(OracleModule::rename(Origin::signed(BOB), id, to_raw(&"test")).is_ok());
+
+        assert!(OracleModule::rename(Origin::ROOT, id + 1, to_raw(&"test")).is_err());
+    });
+}
+
+#[test]
+fn asset_lifecycle()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+        calculate(id, 20, 1);
+
+        assert!(OracleModule::add_asset(Origin::signed(BOB), id, to_raw(&"three"), None).is_err());
+        assert!(OracleModule::add_asset(Origin::signed(ALICE), id, to_raw(&"three"), None).is_ok());
+        assert!(OracleModule::remove_asset(Origin::signed(ALICE), id, 0).is_err());
+        assert!(OracleModule::deprecate_asset(Origin::signed(ALICE), id, 0).is_ok());
+
+        TimestampModule::set_timestamp(25);
+        assert!(OracleModule::commit_sparse(Origin::signed(ALICE), id, vec![(0, 11)]).is_err());
+        assert_eq!(
+            OracleModule::commit(
+                Origin::signed(ALICE),
+                id,
+                AssetsVec {
+                    0: vec![11, 21, 30]
+                }
+            ),
+            Err(Error::AssetsCountMismatch)
+        );
+        assert!(
+            OracleModule::commit(Origin::signed(ALICE), id, AssetsVec { 0: vec![21, 30] }).is_ok()
+        );
+        assert!(OracleModule::remove_asset(Origin::signed(ALICE), id, 0).is_ok());
+        assert_eq!(OracleModule::get_value(id, 0), None);
+        assert!(OracleModule::history_periods((id, 0)).is_empty());
+        assert_eq!(OracleModule::oracles(id).assets_id.0, vec![1, 2]);
+
+        assert!(OracleModule::reorder_assets(Origin::signed(BOB), id, vec![2, 1]).is_err());
+        assert_eq!(
+            OracleModule::reorder_assets(Origin::signed(ALICE), id, vec![2, 0]),
+            Err(Error::WrongAssetId)
+        );
+        assert!(OracleModule::reorder_assets(Origin::signed(ALICE), id, vec![2, 1]).is_ok());
+        assert_eq!(OracleModule::oracles(id).assets_id.0, vec![2, 1]);
+
+        assert_eq!(
+            OracleModule::commit(Origin::signed(ALICE), id, AssetsVec { 0: vec![30, 21] }),
+            Err(Error::AlreadyCommitted)
+        );
+        commit_all(id, 26, vec![30, 21]);
+        calculate(id, 30, 1);
+        calculate(id, 30, 2);
+        assert_eq!(
+            OracleModule::get_value(id, 1).and_then(|value| value.value),
+            Some(21)
         );
-        todo!()
+        assert_eq!(
+            OracleModule::get_value(id, 2).and_then(|value| value.value),
+            Some(30)
+        );
+    });
+}
+
+#[test]
+fn close_oracle()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let table = OracleModule::oracles(id).table;
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_err());
+        assert!(OracleModule::close(Origin::signed(BOB), id).is_err());
+        assert!(OracleModule::close(Origin::signed(ALICE), id).is_ok());
+        assert!(OracleModule::close(Origin::signed(ALICE), id).is_err());
+        assert_eq!(OracleModule::oracles(id).closed_at, Some(20));
+
+        assert!(OracleModule::calculate(Origin::signed(ALICE), id, 1).is_err());
+        TimestampModule::set_timestamp(25);
+        assert!(
+            OracleModule::commit(Origin::signed(ALICE), id, AssetsVec { 0: vec![11, 21] }).is_err()
+        );
+
+        TimestampModule::set_timestamp(119);
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_err());
+
+        TimestampModule::set_timestamp(120);
+        assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &ALICE), 100);
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_ok());
+
+        assert!(!Oracles::<Test>::exists(id));
+        assert!(OracleModule::get_oracles().is_empty());
+        assert!(OracleModule::history_periods((id, 0)).is_empty());
+        assert!(!Scores::<Test>::exists(table));
+        assert!(SOURCES
+            .iter()
+            .all(|source| AssetsModule::reserved_balance(&ASSET_ID, source) == 0));
+        assert!(TablescoreModule::vote(Origin::signed(ALICE), table, 100, ALICE).is_err());
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_err());
+
+        // The removed oracle is not written back to the storage
+        assert_eq!(
+            OracleModule::calculate(Origin::signed(ALICE), id, 0),
+            Err(Error::OracleNotFound)
+        );
+        TimestampModule::set_timestamp(125);
+        assert_eq!(
+            OracleModule::commit(Origin::signed(ALICE), id, AssetsVec { 0: vec![11, 21] }),
+            Err(Error::OracleNotFound)
+        );
+        OracleModule::on_finalize(2);
+        assert!(!Oracles::<Test>::exists(id));
+    });
+}
+
+#[test]
+fn commit_reveal()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let values = AssetsVec {
+            0: vec![10u128, 20],
+        };
+        let salt = to_raw(&"salt");
+        let hash = |source: u64| BlakeTwo256::hash_of(&(id, source, &values, &salt));
+
+        TimestampModule::set_timestamp(15);
+        assert!(OracleModule::commit_hash(Origin::signed(ALICE), id, hash(ALICE)).is_err());
+        assert!(OracleModule::set_reveal_period(Origin::signed(BOB), id, 2).is_err());
+        assert_eq!(
+            OracleModule::set_reveal_period(Origin::signed(ALICE), id, 5),
+            Err(Error::WrongRevealPeriod)
+        );
+        assert!(OracleModule::set_reveal_period(Origin::signed(ALICE), id, 2).is_ok());
+
+        assert!(OracleModule::commit(Origin::signed(ALICE), id, values.clone()).is_err());
+        assert!(
+            OracleModule::reveal(Origin::signed(ALICE), id, values.clone(), salt.clone()).is_err()
+        );
+        for source in SOURCES.iter()
+        {
+            assert!(OracleModule::commit_hash(Origin::signed(*source), id, hash(*source)).is_ok());
+        }
+        assert!(OracleModule::calculate(Origin::signed(ALICE), id, 0).is_err());
+
+        TimestampModule::set_timestamp(18);
+        assert!(OracleModule::commit_hash(Origin::signed(ALICE), id, hash(ALICE)).is_err());
+        assert!(
+            OracleModule::reveal(Origin::signed(ALICE), id, values.clone(), to_raw(&"other"))
+                .is_err()
+        );
+        for source in SOURCES.iter()
+        {
+            assert!(OracleModule::reveal(
+                Origin::signed(*source),
+                id,
+                values.clone(),
+                salt.clone()
+            )
+            .is_ok());
+        }
+
+        calculate(id, 20, 0);
+        assert_eq!(
+            OracleModule::get_value(id, 0).and_then(|value| value.value),
+            Some(10)
+        );
+
+        // A commitment copied from another source can't be revealed
+        TimestampModule::set_timestamp(25);
+        assert!(OracleModule::commit_hash(Origin::signed(ALICE), id, hash(ALICE)).is_ok());
+        assert!(OracleModule::commit_hash(Origin::signed(CAROL), id, hash(ALICE)).is_ok());
+
+        TimestampModule::set_timestamp(28);
+        assert!(
+            OracleModule::reveal(Origin::signed(ALICE), id, values.clone(), salt.clone()).is_ok()
+        );
+        assert_eq!(
+            OracleModule::reveal(Origin::signed(CAROL), id, values.clone(), salt.clone()),
+            Err(Error::WrongReveal)
+        );
+    });
+}
+
+#[test]
+fn slash_deviating_sources()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let table = OracleModule::oracles(id).table;
+        let rule = SlashRule {
+            max_deviation: Permill::from_percent(10),
+            slash: Permill::from_percent(50),
+        };
+        assert!(OracleModule::set_slash_rule(Origin::signed(BOB), id, Some(rule)).is_err());
+        assert!(OracleModule::set_slash_rule(Origin::signed(ALICE), id, Some(rule)).is_ok());
+
+        TimestampModule::set_timestamp(15);
+        for (source, value) in SOURCES.iter().zip(vec![100, 105, 200].into_iter())
+        {
+            assert!(OracleModule::commit(
+                Origin::signed(*source),
+                id,
+                AssetsVec {
+                    0: vec![value, value / 100]
+                }
+            )
+            .is_ok());
+        }
+
+        calculate(id, 20, 0);
+        assert_eq!(
+            OracleModule::get_value(id, 0).and_then(|value| value.value),
+            Some(105)
+        );
+        assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &ALICE), 100);
+        assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &CAROL), 50);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &TREASURY), 50);
+        assert_eq!(TablescoreModule::scores(table).get_target_stake(&CAROL), 50);
+
+        // Deviating values of other assets don't slash the source again in the period
+        calculate(id, 20, 1);
+        assert_eq!(
+            OracleModule::get_value(id, 1).and_then(|value| value.value),
+            Some(1)
+        );
+        assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &CAROL), 50);
+
+        // Stale values are not slashed again
+        Oracles::<Test>::mutate(id, |oracle| oracle.staleness = StalenessPolicy::Any);
+        calculate(id, 30, 0);
+        assert_eq!(
+            OracleModule::get_value(id, 0).and_then(|value| value.value),
+            Some(105)
+        );
+        assert_eq!(AssetsModule::reserved_balance(&ASSET_ID, &CAROL), 50);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &TREASURY), 50);
+    });
+}
+
+#[test]
+fn reward_sources()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let rule = RewardRule {
+            asset_id: ASSET_ID,
+            per_period: 60,
+            max_deviation: Permill::from_percent(10),
+        };
+        assert_eq!(
+            OracleModule::top_up(Origin::signed(BOB), id, 100),
+            Err(Error::NoRewardRule)
+        );
+        assert!(OracleModule::set_reward_rule(Origin::signed(BOB), id, Some(rule)).is_err());
+        assert!(OracleModule::set_reward_rule(Origin::signed(ALICE), id, Some(rule)).is_ok());
+
+        assert_eq!(
+            OracleModule::top_up(Origin::signed(BOB), id, 0),
+            Err(Error::ZeroTopUp)
+        );
+        assert!(OracleModule::top_up(Origin::signed(BOB), id, 100).is_ok());
+        assert_eq!(OracleModule::reward_pool(id), 100);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &BOB), 800);
+        assert_eq!(
+            AssetsModule::free_balance(&ASSET_ID, &OracleModule::reward_pot()),
+            100
+        );
+        assert!(OracleModule::set_reward_rule(
+            Origin::signed(ALICE),
+            id,
+            Some(RewardRule {
+                asset_id: ASSET_ID + 1,
+                ..rule
+            })
+        )
+        .is_err());
+
+        TimestampModule::set_timestamp(15);
+        for (source, value) in SOURCES.iter().zip(vec![100, 105, 200].into_iter())
+        {
+            assert!(OracleModule::commit(
+                Origin::signed(*source),
+                id,
+                AssetsVec { 0: vec![value, 1] }
+            )
+            .is_ok());
+        }
+
+        // Half of the period reward for every asset, CAROL's value of the first one deviates
+        calculate(id, 20, 0);
+        calculate(id, 20, 1);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &ALICE), 925);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &BOB), 825);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &CAROL), 910);
+        assert_eq!(OracleModule::reward_pool(id), 40);
+
+        // The pool is decreased only by the paid rewards
+        let pot = OracleModule::reward_pot();
+        assert!(AssetsModule::make_transfer(&ASSET_ID, &pot, &TREASURY, 30).is_ok());
+        commit_all(id, 25, vec![100, 1]);
+        calculate(id, 30, 0);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &ALICE), 935);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &BOB), 825);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &CAROL), 910);
+        assert_eq!(OracleModule::reward_pool(id), 30);
+        assert!(AssetsModule::make_transfer(&ASSET_ID, &TREASURY, &pot, 30).is_ok());
+
+        assert!(OracleModule::close(Origin::signed(ALICE), id).is_ok());
+        TimestampModule::set_timestamp(130);
+
+        // A failed return of the pool keeps the oracle
+        assert!(AssetsModule::make_transfer(&ASSET_ID, &pot, &BOB, 30).is_ok());
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_err());
+        assert!(Oracles::<Test>::exists(id));
+        assert_eq!(OracleModule::reward_pool(id), 30);
+        assert!(!OracleModule::history_periods((id, 0)).is_empty());
+
+        assert!(AssetsModule::make_transfer(&ASSET_ID, &BOB, &pot, 30).is_ok());
+        assert!(OracleModule::cleanup(Origin::signed(BOB), id).is_ok());
+        assert_eq!(OracleModule::reward_pool(id), 0);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &ALICE), 965);
+    });
+}
+
+#[test]
+fn share_rewards_with_voters()
+{
+    new_test_ext().execute_with(|| {
+        let id = OracleModule::next_oracle_id();
+        assert!(OracleModule::create(
+            Origin::signed(ALICE),
+            to_raw(&"test"),
+            ASSET_ID,
+            2,
+            5,
+            10,
+            AggregatorType::Median,
+            OutlierFilter::Disabled,
+            StalenessPolicy::CurrentWindow,
+            AssetsVec {
+                0: ["one"].iter().map(to_raw).collect(),
+            },
+            None,
+        )
+        .is_ok());
+
+        let table = OracleModule::oracles(id).table;
+        assert!(TablescoreModule::vote(Origin::signed(ALICE), table, 100, ALICE).is_ok());
+        assert!(TablescoreModule::vote(Origin::signed(BOB), table, 100, BOB).is_ok());
+        assert!(TablescoreModule::vote(Origin::signed(CAROL), table, 300, ALICE).is_ok());
+
+        assert!(
+            OracleModule::set_commission(Origin::signed(ALICE), Permill::from_percent(20)).is_ok()
+        );
+        assert_eq!(
+            OracleModule::source_commission(ALICE),
+            Permill::from_percent(20)
+        );
+
+        let rule = RewardRule {
+            asset_id: ASSET_ID,
+            per_period: 40,
+            max_deviation: Permill::from_percent(10),
+        };
+        assert!(OracleModule::set_reward_rule(Origin::signed(ALICE), id, Some(rule)).is_ok());
+        assert!(OracleModule::top_up(Origin::signed(BOB), id, 100).is_ok());
+
+        TimestampModule::set_timestamp(15);
+        for source in [ALICE, BOB].iter()
+        {
+            assert!(
+                OracleModule::commit(Origin::signed(*source), id, AssetsVec { 0: vec![100] })
+                    .is_ok()
+            );
+        }
+        assert!(
+            OracleModule::commit(Origin::signed(CAROL), id, AssetsVec { 0: vec![100] }).is_err()
+        );
+
+        // ALICE keeps 4 of 20 and shares 16 with her voters as 100 to 300
+        calculate(id, 20, 0);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &ALICE), 908);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &BOB), 820);
+        assert_eq!(AssetsModule::free_balance(&ASSET_ID, &CAROL), 712);
+        assert_eq!(OracleModule::reward_pool(id), 60);
+    });
+}
+
+fn events() -> Vec<TestEvent>
+{
+    System::events()
+        .into_iter()
+        .map(|record| record.event)
+        .collect()
+}
+
+#[test]
+fn deposit_events()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        let table = OracleModule::oracles(id).table;
+        let created = events();
+        assert!(created.contains(&TestEvent::module(RawEvent::OracleCreated(id, ALICE))));
+        assert!(created.contains(&TestEvent::tablescore(TablescoreEvent::TableCreated(table))));
+        assert!(
+            created.contains(&TestEvent::tablescore(TablescoreEvent::Voted(
+                table, BOB, BOB, 100
+            )))
+        );
+        assert_eq!(
+            created.last(),
+            Some(&TestEvent::tablescore(TablescoreEvent::HeadChanged(
+                table,
+                vec![ALICE, BOB, CAROL]
+            )))
+        );
+
+        commit_all(id, 15, vec![10, 20]);
+        let committed = events();
+        assert!(
+            committed.contains(&TestEvent::module(RawEvent::SourcesUpdated(
+                id,
+                vec![ALICE, BOB, CAROL]
+            )))
+        );
+        assert_eq!(
+            committed.last(),
+            Some(&TestEvent::module(RawEvent::ValueCommitted(
+                id,
+                CAROL,
+                vec![(0, 10), (1, 20)]
+            )))
+        );
+
+        calculate(id, 20, 0);
+        assert_eq!(
+            events().last(),
+            Some(&TestEvent::module(RawEvent::ValueCalculated(id, 0, 10, 2)))
+        );
+
+        assert!(TablescoreModule::unvote(Origin::signed(CAROL), table).is_ok());
+        let unvoted = events();
+        assert!(
+            unvoted.contains(&TestEvent::tablescore(TablescoreEvent::Unvoted(
+                table, CAROL, CAROL, 100
+            )))
+        );
+        assert_eq!(
+            unvoted.last(),
+            Some(&TestEvent::tablescore(TablescoreEvent::HeadChanged(
+                table,
+                vec![ALICE, BOB]
+            )))
+        );
+    });
+}
+
+#[test]
+fn runtime_api_helpers()
+{
+    new_test_ext().execute_with(|| {
+        let id = create_oracle_with_sources();
+        assert_eq!(OracleModule::get_oracles(), vec![(id, to_raw(&"test"))]);
+        assert_eq!(OracleModule::get_current_period(id + 1), None);
+        assert!(!OracleModule::can_commit(id + 1, &ALICE));
+        assert!(OracleModule::get_current_sources(id + 1).is_empty());
+
+        TimestampModule::set_timestamp(12);
+        assert_eq!(OracleModule::get_current_period(id), Some(1));
+        assert!(!OracleModule::is_aggregate_time(id));
+        assert_eq!(OracleModule::get_time_to_aggregate(id), Some(3));
+        assert!(!OracleModule::can_commit(id, &ALICE));
+        assert_eq!(
+            OracleModule::get_assets(id),
+            vec![(0, to_raw(&"one"), false), (1, to_raw(&"two"), false)]
+        );
+
+        TimestampModule::set_timestamp(15);
+        assert_eq!(OracleModule::get_time_to_aggregate(id), Some(0));
+        assert_eq!(
+            OracleModule::get_current_sources(id),
+            vec![ALICE, BOB, CAROL]
+        );
+        assert!(OracleModule::can_commit(id, &ALICE));
+        assert!(!OracleModule::can_commit(id, &TREASURY));
+
+        commit_all(id, 15, vec![10, 20]);
+        calculate(id, 20, 0);
+        let values = OracleModule::get_values(id);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].0, 0);
+        assert_eq!(values[0].1.value, Some(10));
+        assert_eq!(values[1].1.value, None);
+
+        assert!(OracleModule::close(Origin::signed(ALICE), id).is_ok());
+        assert!(!OracleModule::is_aggregate_time(id));
+        assert_eq!(OracleModule::get_time_to_aggregate(id), None);
+    });
+}
+
+#[test]
+fn offchain_worker()
+{
+    let (offchain, offchain_state) = TestOffchainExt::new();
+    let (pool, pool_state) = TestTransactionPoolExt::new();
+    let mut ext = new_test_ext();
+    ext.register_extension(OffchainExt::new(offchain));
+    ext.register_extension(TransactionPoolExt::new(pool));
+    UintAuthorityId::set_all_keys(vec![ALICE, TREASURY]);
+
+    ext.execute_with(|| {
+        let id = create_oracle_with_sources();
+        let endpoints = vec![
+            Endpoint {
+                asset_id: 0,
+                url: to_raw(&"http://localhost/one"),
+                path: vec![to_raw(&"data"), to_raw(&"price")],
+                decimals: 2,
+            },
+            Endpoint {
+                asset_id: 1,
+                url: to_raw(&"http://localhost/two"),
+                path: vec![],
+                decimals: 0,
+            },
+        ];
+        runtime_io::offchain::local_storage_set(
+            StorageKind::PERSISTENT,
+            &[ENDPOINTS_KEY, &id.encode()].concat(),
+            &endpoints.encode(),
+        );
+
+        TimestampModule::set_timestamp(12);
+        OracleModule::commit_offchain_values(12);
+        assert!(pool_state.read().transactions.is_empty());
+
+        {
+            let mut state = offchain_state.write();
+            state.expect_request(
+                0,
+                PendingRequest {
+                    method: "GET".into(),
+                    uri: "http://localhost/one".into(),
+                    response: Some(br#"{"data": {"price": "10.5"}}"#.to_vec()),
+                    sent: true,
+                    ..Default::default()
+                },
+            );
+            state.expect_request(
+                1,
+                PendingRequest {
+                    method: "GET".into(),
+                    uri: "http://localhost/two".into(),
+                    response: Some(b"20".to_vec()),
+                    sent: true,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // Only ALICE is a source, TREASURY doesn't commit
+        TimestampModule::set_timestamp(15);
+        OracleModule::commit_offchain_values(15);
+        let transaction = pool_state.write().transactions.pop().unwrap();
+        assert!(pool_state.read().transactions.is_empty());
+
+        let transaction = Extrinsic::decode(&mut &*transaction).unwrap();
+        assert_eq!(transaction.0, Some((ALICE, ())));
+        assert_eq!(transaction.1, Call::commit(id, AssetsVec(vec![1050, 20])));
+
+        // Values are committed once in a period
+        TimestampModule::set_timestamp(16);
+        OracleModule::commit_offchain_values(16);
+        assert!(pool_state.read().transactions.is_empty());
     });
 }
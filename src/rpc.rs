@@ -0,0 +1,698 @@
+//! JSON-RPC methods of the oracle and tablescore modules.
+//!
+//! Values and balances are `u128`, they are returned as decimal strings since
+//! they don't fit into JSON numbers.
+
+use std::sync::Arc;
+
+use futures::future::{ExecuteError, ExecuteErrorKind, Executor};
+use futures::{stream, Future, Sink, Stream};
+use futures03::{future, StreamExt as _, TryStreamExt as _};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
+use log::warn;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sr_primitives::generic::BlockId;
+use sr_primitives::traits::ProvideRuntimeApi;
+use substrate_client::{blockchain::HeaderBackend, BlockchainEvents};
+use substrate_rpc_api::Subscriptions;
+use tablescore_module_runtime::{
+    module::ExternalValue,
+    opaque::Block,
+    runtime_api::{OracleApi as OracleRuntimeApi, TablescoreApi as TablescoreRuntimeApi},
+    AccountId, Balance, Hash, Moment, OracleId, Runtime, TableId,
+};
+
+/// Error code of the failed runtime API calls.
+const RUNTIME_ERROR: i64 = 1;
+/// Error code of the calls for an oracle that doesn't exist.
+const ORACLE_NOT_FOUND: i64 = 2;
+
+/// Extension of the node RPC with the oracle and tablescore methods.
+pub type RpcExtension = jsonrpc_core::IoHandler<substrate_rpc::Metadata>;
+
+/// Task that sends the notifications of a subscription.
+type SubscriptionTask = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Executor of the subscription tasks. The RPC extension is created while the service
+/// is built, so the task executor of the service is set after it; tasks spawned before
+/// that are rejected.
+#[derive(Clone, Default)]
+pub struct SubscriptionsExecutor(Arc<Mutex<Option<Box<dyn Executor<SubscriptionTask> + Send>>>>);
+
+impl SubscriptionsExecutor
+{
+    pub fn set<E: Executor<SubscriptionTask> + Send + 'static>(&self, executor: E)
+    {
+        *self.0.lock() = Some(Box::new(executor));
+    }
+}
+
+impl Executor<SubscriptionTask> for SubscriptionsExecutor
+{
+    fn execute(
+        &self,
+        task: SubscriptionTask,
+    ) -> std::result::Result<(), ExecuteError<SubscriptionTask>>
+    {
+        match self.0.lock().as_ref()
+        {
+            Some(executor) => executor.execute(task),
+            None => Err(ExecuteError::new(ExecuteErrorKind::Shutdown, task)),
+        }
+    }
+}
+
+/// Oracle with its id.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OracleInfo
+{
+    pub id: OracleId,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset
+{
+    pub id: u8,
+    pub name: String,
+    pub deprecated: bool,
+}
+
+/// Statistics of the source values that a value is calculated from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueMetadata
+{
+    pub sources_count: u32,
+    pub min: String,
+    pub max: String,
+    pub mad: String,
+    pub std_dev: String,
+}
+
+/// Calculated value of an oracle asset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetValue
+{
+    pub asset_id: u8,
+    pub name: String,
+    pub value: Option<String>,
+    pub last_changed: Option<Moment>,
+    pub metadata: Option<ValueMetadata>,
+}
+
+impl AssetValue
+{
+    pub fn new(asset_id: u8, name: String, value: ExternalValue<Runtime>) -> AssetValue
+    {
+        AssetValue {
+            asset_id,
+            name,
+            value: value.value.map(|value| value.to_string()),
+            last_changed: value.last_changed,
+            metadata: value.metadata.map(|metadata| ValueMetadata {
+                sources_count: metadata.sources_count,
+                min: metadata.min.to_string(),
+                max: metadata.max.to_string(),
+                mad: metadata.mad.to_string(),
+                std_dev: metadata.std_dev.to_string(),
+            }),
+        }
+    }
+}
+
+/// Values of the subscribed assets that changed in the block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValuesChange
+{
+    pub block: Hash,
+    pub values: Vec<AssetValue>,
+}
+
+/// Target of a table with its stake, or a vote with its balance.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Score
+{
+    pub target: AccountId,
+    pub balance: String,
+}
+
+impl From<(AccountId, Balance)> for Score
+{
+    fn from((target, balance): (AccountId, Balance)) -> Score
+    {
+        Score {
+            target,
+            balance: balance.to_string(),
+        }
+    }
+}
+
+#[rpc]
+pub trait OracleApi<BlockHash>
+{
+    type Metadata;
+
+    /// Ids and names of all oracles.
+    #[rpc(name = "oracle_getOracles")]
+    fn get_oracles(&self, at: Option<BlockHash>) -> Result<Vec<OracleInfo>>;
+
+    /// Assets of the oracle.
+    #[rpc(name = "oracle_getAssets")]
+    fn get_assets(&self, oracle_id: OracleId, at: Option<BlockHash>) -> Result<Vec<Asset>>;
+
+    /// Calculated values of all assets of the oracle.
+    #[rpc(name = "oracle_getValues")]
+    fn get_values(&self, oracle_id: OracleId, at: Option<BlockHash>) -> Result<Vec<AssetValue>>;
+
+    /// Notifies about the values of the assets, all assets if `assets` is empty.
+    /// The first notification has the current values, the next ones the values
+    /// recalculated in a new best block, or in a finalized block if `finalized` is set.
+    /// Notifications stop when the oracle is removed.
+    #[pubsub(
+        subscription = "oracle_values",
+        subscribe,
+        name = "oracle_subscribeValues"
+    )]
+    fn subscribe_values(
+        &self,
+        metadata: Self::Metadata,
+        subscriber: Subscriber<ValuesChange>,
+        oracle_id: OracleId,
+        assets: Option<Vec<u8>>,
+        finalized: Option<bool>,
+    );
+
+    #[pubsub(
+        subscription = "oracle_values",
+        unsubscribe,
+        name = "oracle_unsubscribeValues"
+    )]
+    fn unsubscribe_values(
+        &self,
+        metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool>;
+}
+
+#[rpc]
+pub trait TablescoreApi<BlockHash>
+{
+    /// Targets of the table head with their stakes.
+    #[rpc(name = "tablescore_getHead")]
+    fn get_head(&self, table_id: TableId, at: Option<BlockHash>) -> Result<Vec<Score>>;
+
+    /// All voted targets of the table with their stakes, the largest stake first.
+    #[rpc(name = "tablescore_getLeaderboard")]
+    fn get_leaderboard(&self, table_id: TableId, at: Option<BlockHash>) -> Result<Vec<Score>>;
+
+    /// Current vote of the voter.
+    #[rpc(name = "tablescore_getVote")]
+    fn get_vote(
+        &self,
+        table_id: TableId,
+        voter: AccountId,
+        at: Option<BlockHash>,
+    ) -> Result<Option<Score>>;
+}
+
+fn runtime_error<E: std::fmt::Debug>(error: E) -> RpcError
+{
+    RpcError {
+        code: ErrorCode::ServerError(RUNTIME_ERROR),
+        message: "Runtime API call failed.".into(),
+        data: Some(format!("{:?}", error).into()),
+    }
+}
+
+/// The best block if `at` is not set.
+fn block_id<C: HeaderBackend<Block>>(client: &C, at: Option<Hash>) -> BlockId<Block>
+{
+    BlockId::hash(at.unwrap_or_else(|| client.info().best_hash))
+}
+
+fn to_string(name: Vec<u8>) -> String
+{
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+fn oracle_not_found(oracle_id: OracleId) -> RpcError
+{
+    RpcError {
+        code: ErrorCode::ServerError(ORACLE_NOT_FOUND),
+        message: "Oracle not found.".into(),
+        data: Some(oracle_id.into()),
+    }
+}
+
+/// Values of the oracle assets in `assets`, all values if it is empty.
+/// `None` if the oracle doesn't exist.
+fn get_values<C>(
+    client: &C,
+    oracle_id: OracleId,
+    assets: &[u8],
+    at: &BlockId<Block>,
+) -> Result<Option<Vec<AssetValue>>>
+where
+    C: ProvideRuntimeApi,
+    C::Api: OracleRuntimeApi<Block, AccountId, OracleId, ExternalValue<Runtime>, Moment>,
+{
+    let api = client.runtime_api();
+    let oracles = api.get_oracles(at).map_err(runtime_error)?;
+    if !oracles.iter().any(|(id, _)| *id == oracle_id)
+    {
+        return Ok(None);
+    }
+
+    let names = api.get_assets(at, oracle_id).map_err(runtime_error)?;
+    let values = api.get_values(at, oracle_id).map_err(runtime_error)?;
+
+    Ok(Some(to_asset_values(names, values, assets)))
+}
+
+/// Pairs the assets of an oracle with its values, both are in the order of the oracle assets.
+/// Only the assets in `assets` are kept, all assets if it is empty.
+fn to_asset_values(
+    names: Vec<(u8, Vec<u8>, bool)>,
+    values: Vec<(u8, ExternalValue<Runtime>)>,
+    assets: &[u8],
+) -> Vec<AssetValue>
+{
+    names
+        .into_iter()
+        .zip(values.into_iter())
+        .filter(|((asset_id, _, _), _)| assets.is_empty() || assets.contains(asset_id))
+        .map(|((asset_id, name, _), (_, value))| AssetValue::new(asset_id, to_string(name), value))
+        .collect()
+}
+
+/// First asset of `assets` that has no value in `values`.
+fn find_unknown_asset(values: &[AssetValue], assets: &[u8]) -> Option<u8>
+{
+    assets
+        .iter()
+        .find(|asset_id| !values.iter().any(|value| value.asset_id == **asset_id))
+        .cloned()
+}
+
+/// Values that differ from `last_values`. A value is changed when it is recalculated,
+/// even to the same value.
+fn get_changed_values(last_values: &[AssetValue], values: &[AssetValue]) -> Vec<AssetValue>
+{
+    values
+        .iter()
+        .filter(|value| !last_values.contains(value))
+        .cloned()
+        .collect()
+}
+
+pub struct Oracle<C>
+{
+    client: Arc<C>,
+    subscriptions: Subscriptions,
+}
+
+impl<C> Oracle<C>
+{
+    pub fn new(client: Arc<C>, subscriptions: Subscriptions) -> Self
+    {
+        Oracle {
+            client,
+            subscriptions,
+        }
+    }
+}
+
+impl<C> OracleApi<Hash> for Oracle<C>
+where
+    C: ProvideRuntimeApi + HeaderBackend<Block> + BlockchainEvents<Block>,
+    C: Send + Sync + 'static,
+    C::Api: OracleRuntimeApi<Block, AccountId, OracleId, ExternalValue<Runtime>, Moment>,
+{
+    type Metadata = substrate_rpc::Metadata;
+
+    fn get_oracles(&self, at: Option<Hash>) -> Result<Vec<OracleInfo>>
+    {
+        let oracles = self
+            .client
+            .runtime_api()
+            .get_oracles(&block_id(&*self.client, at))
+            .map_err(runtime_error)?;
+
+        Ok(oracles
+            .into_iter()
+            .map(|(id, name)| OracleInfo {
+                id,
+                name: to_string(name),
+            })
+            .collect())
+    }
+
+    fn get_assets(&self, oracle_id: OracleId, at: Option<Hash>) -> Result<Vec<Asset>>
+    {
+        let assets = self
+            .client
+            .runtime_api()
+            .get_assets(&block_id(&*self.client, at), oracle_id)
+            .map_err(runtime_error)?;
+
+        Ok(assets
+            .into_iter()
+            .map(|(id, name, deprecated)| Asset {
+                id,
+                name: to_string(name),
+                deprecated,
+            })
+            .collect())
+    }
+
+    fn get_values(&self, oracle_id: OracleId, at: Option<Hash>) -> Result<Vec<AssetValue>>
+    {
+        get_values(&*self.client, oracle_id, &[], &block_id(&*self.client, at))?
+            .ok_or_else(|| oracle_not_found(oracle_id))
+    }
+
+    fn subscribe_values(
+        &self,
+        _metadata: Self::Metadata,
+        subscriber: Subscriber<ValuesChange>,
+        oracle_id: OracleId,
+        assets: Option<Vec<u8>>,
+        finalized: Option<bool>,
+    )
+    {
+        let assets = assets.unwrap_or_default();
+        let finalized = finalized.unwrap_or(false);
+
+        let info = self.client.info();
+        let block = if finalized
+        {
+            info.finalized_hash
+        }
+        else
+        {
+            info.best_hash
+        };
+        let initial = match get_values(&*self.client, oracle_id, &assets, &BlockId::hash(block))
+            .and_then(|values| values.ok_or_else(|| oracle_not_found(oracle_id)))
+        {
+            Ok(values) => ValuesChange { block, values },
+            Err(error) =>
+            {
+                let _ = subscriber.reject(error);
+                return;
+            }
+        };
+        if let Some(asset_id) = find_unknown_asset(&initial.values, &assets)
+        {
+            let _ = subscriber.reject(RpcError::invalid_params(format!(
+                "Unknown asset id {}.",
+                asset_id
+            )));
+            return;
+        }
+
+        let blocks: Box<dyn Stream<Item = Hash, Error = ()> + Send> = if finalized
+        {
+            Box::new(
+                self.client
+                    .finality_notification_stream()
+                    .map(|notification| Ok::<_, ()>(notification.hash))
+                    .compat(),
+            )
+        }
+        else
+        {
+            Box::new(
+                self.client
+                    .import_notification_stream()
+                    .filter(|notification| future::ready(notification.is_new_best))
+                    .map(|notification| Ok::<_, ()>(notification.hash))
+                    .compat(),
+            )
+        };
+
+        let client = self.client.clone();
+        let mut last_values = initial.values.clone();
+        let changes = blocks
+            .map(move |block| {
+                let changed = match get_values(&*client, oracle_id, &assets, &BlockId::hash(block))
+                {
+                    Ok(Some(values)) =>
+                    {
+                        let changed = get_changed_values(&last_values, &values);
+                        last_values = values;
+                        Some(changed)
+                    }
+                    Ok(None) => None,
+                    Err(error) =>
+                    {
+                        warn!("Failed to get values of oracle {}: {:?}", oracle_id, error);
+                        Some(Vec::new())
+                    }
+                };
+                changed.map(|values| ValuesChange { block, values })
+            })
+            // The subscription ends when the oracle is removed
+            .take_while(|change| Ok(change.is_some()))
+            .filter_map(|change| change.filter(|change| !change.values.is_empty()))
+            .map(Ok::<_, RpcError>);
+
+        self.subscriptions.add(subscriber, move |sink| {
+            sink.sink_map_err(|error| warn!("Error sending notifications: {:?}", error))
+                .send_all(stream::iter_result(vec![Ok(Ok(initial))]).chain(changes))
+                .map(|_| ())
+        });
+    }
+
+    fn unsubscribe_values(
+        &self,
+        _metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool>
+    {
+        Ok(self.subscriptions.cancel(id))
+    }
+}
+
+pub struct Tablescore<C>
+{
+    client: Arc<C>,
+}
+
+impl<C> Tablescore<C>
+{
+    pub fn new(client: Arc<C>) -> Self
+    {
+        Tablescore { client }
+    }
+}
+
+impl<C> TablescoreApi<Hash> for Tablescore<C>
+where
+    C: ProvideRuntimeApi + HeaderBackend<Block> + Send + Sync + 'static,
+    C::Api: TablescoreRuntimeApi<Block, AccountId, TableId, AccountId, Balance>,
+{
+    fn get_head(&self, table_id: TableId, at: Option<Hash>) -> Result<Vec<Score>>
+    {
+        let head = self
+            .client
+            .runtime_api()
+            .get_head(&block_id(&*self.client, at), table_id)
+            .map_err(runtime_error)?;
+
+        Ok(head.into_iter().map(Score::from).collect())
+    }
+
+    fn get_leaderboard(&self, table_id: TableId, at: Option<Hash>) -> Result<Vec<Score>>
+    {
+        let leaderboard = self
+            .client
+            .runtime_api()
+            .get_leaderboard(&block_id(&*self.client, at), table_id)
+            .map_err(runtime_error)?;
+
+        Ok(leaderboard.into_iter().map(Score::from).collect())
+    }
+
+    fn get_vote(
+        &self,
+        table_id: TableId,
+        voter: AccountId,
+        at: Option<Hash>,
+    ) -> Result<Option<Score>>
+    {
+        let vote = self
+            .client
+            .runtime_api()
+            .get_vote(&block_id(&*self.client, at), table_id, voter)
+            .map_err(runtime_error)?;
+
+        Ok(vote.map(Score::from))
+    }
+}
+
+/// Creates the oracle and tablescore RPC extension of a node. Subscriptions are
+/// run by `executor`, which is set to the task executor of the service.
+pub fn create<C>(client: Arc<C>, executor: SubscriptionsExecutor) -> RpcExtension
+where
+    C: ProvideRuntimeApi + HeaderBackend<Block> + BlockchainEvents<Block>,
+    C: Send + Sync + 'static,
+    C::Api: OracleRuntimeApi<Block, AccountId, OracleId, ExternalValue<Runtime>, Moment>
+        + TablescoreRuntimeApi<Block, AccountId, TableId, AccountId, Balance>,
+{
+    let mut io = RpcExtension::default();
+    let subscriptions = Subscriptions::new(Arc::new(executor));
+    io.extend_with(OracleApi::to_delegate(Oracle::new(
+        client.clone(),
+        subscriptions,
+    )));
+    io.extend_with(TablescoreApi::to_delegate(Tablescore::new(client)));
+    io
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tablescore_module_runtime::module::ValueMetadata as RuntimeValueMetadata;
+
+    fn calculated(value: u128, last_changed: Moment) -> ExternalValue<Runtime>
+    {
+        ExternalValue {
+            value: Some(value),
+            last_changed: Some(last_changed),
+            metadata: Some(RuntimeValueMetadata {
+                sources_count: 3,
+                min: value - 1,
+                max: value + 1,
+                mad: 1,
+                std_dev: u128::max_value(),
+            }),
+        }
+    }
+
+    #[test]
+    fn asset_value()
+    {
+        assert_eq!(
+            AssetValue::new(1, "one".into(), calculated(100, 20)),
+            AssetValue {
+                asset_id: 1,
+                name: "one".into(),
+                value: Some("100".into()),
+                last_changed: Some(20),
+                metadata: Some(ValueMetadata {
+                    sources_count: 3,
+                    min: "99".into(),
+                    max: "101".into(),
+                    mad: "1".into(),
+                    std_dev: u128::max_value().to_string(),
+                }),
+            }
+        );
+        assert_eq!(
+            AssetValue::new(2, "two".into(), ExternalValue::default()),
+            AssetValue {
+                asset_id: 2,
+                name: "two".into(),
+                value: None,
+                last_changed: None,
+                metadata: None,
+            }
+        );
+    }
+
+    #[test]
+    fn score()
+    {
+        let target = AccountId::from([1u8; 32]);
+        assert_eq!(
+            Score::from((target.clone(), u128::max_value())),
+            Score {
+                target,
+                balance: "340282366920938463463374607431768211455".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn asset_values()
+    {
+        let names = vec![
+            (0, b"one".to_vec(), false),
+            (2, b"two".to_vec(), true),
+            (3, b"three".to_vec(), false),
+        ];
+        let values = vec![
+            (0, calculated(100, 20)),
+            (2, ExternalValue::default()),
+            (3, calculated(300, 30)),
+        ];
+
+        let all = to_asset_values(names.clone(), values.clone(), &[]);
+        assert_eq!(
+            all.iter()
+                .map(|value| (value.asset_id, value.name.as_str(), value.value.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, "one", Some("100".into())),
+                (2, "two", None),
+                (3, "three", Some("300".into())),
+            ]
+        );
+
+        assert_eq!(
+            to_asset_values(names.clone(), values.clone(), &[3, 5]),
+            vec![all[2].clone()]
+        );
+        assert!(to_asset_values(names, values, &[5]).is_empty());
+    }
+
+    #[test]
+    fn unknown_asset()
+    {
+        let values = vec![
+            AssetValue::new(0, "one".into(), calculated(100, 20)),
+            AssetValue::new(2, "two".into(), ExternalValue::default()),
+        ];
+
+        assert_eq!(find_unknown_asset(&values, &[]), None);
+        assert_eq!(find_unknown_asset(&values, &[2, 0]), None);
+        assert_eq!(find_unknown_asset(&values, &[0, 1, 3]), Some(1));
+        assert_eq!(find_unknown_asset(&[], &[0]), Some(0));
+    }
+
+    #[test]
+    fn changed_values()
+    {
+        let last_values = vec![
+            AssetValue::new(0, "one".into(), calculated(100, 20)),
+            AssetValue::new(1, "two".into(), ExternalValue::default()),
+            AssetValue::new(2, "three".into(), calculated(300, 20)),
+        ];
+        assert!(get_changed_values(&last_values, &last_values).is_empty());
+
+        // Recalculated to the same value and calculated for the first time
+        let values = vec![
+            AssetValue::new(0, "one".into(), calculated(100, 30)),
+            AssetValue::new(1, "two".into(), calculated(200, 30)),
+            AssetValue::new(2, "three".into(), calculated(300, 20)),
+        ];
+        assert_eq!(
+            get_changed_values(&last_values, &values),
+            values[..2].to_vec()
+        );
+
+        // Removed assets are not reported
+        assert!(get_changed_values(&values, &values[1..]).is_empty());
+    }
+}
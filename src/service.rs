@@ -36,6 +36,7 @@ macro_rules! new_full_start {
     ($config:expr) => {{
         let mut import_setup = None;
         let inherent_data_providers = inherents::InherentDataProviders::new();
+        let rpc_executor = crate::rpc::SubscriptionsExecutor::default();
 
         let builder = substrate_service::ServiceBuilder::new_full::<
             tablescore_module_runtime::opaque::Block,
@@ -76,9 +77,15 @@ macro_rules! new_full_start {
             import_setup = Some((grandpa_block_import, grandpa_link));
 
             Ok(import_queue)
+        })?
+        .with_rpc_extensions({
+            let rpc_executor = rpc_executor.clone();
+            move |client, _pool, _backend| -> crate::rpc::RpcExtension {
+                crate::rpc::create(client, rpc_executor)
+            }
         })?;
 
-        (builder, import_setup, inherent_data_providers)
+        (builder, import_setup, inherent_data_providers, rpc_executor)
     }};
 }
 
@@ -97,7 +104,8 @@ pub fn new_full<C: Send + Default + 'static>(
     // never actively participate in any consensus process.
     let participates_in_consensus = is_authority && !config.sentry_mode;
 
-    let (builder, mut import_setup, inherent_data_providers) = new_full_start!(config);
+    let (builder, mut import_setup, inherent_data_providers, rpc_executor) =
+        new_full_start!(config);
 
     let (block_import, grandpa_link) = import_setup.take().expect(
         "Link Half and Block Import are present for Full Services or setup failed before. qed",
@@ -110,6 +118,9 @@ pub fn new_full<C: Send + Default + 'static>(
         })?
         .build()?;
 
+    // Subscriptions of the oracle RPC run on the tasks of the service.
+    rpc_executor.set(service.spawn_task_handle());
+
     if participates_in_consensus
     {
         let proposer = basic_authorship::ProposerFactory {